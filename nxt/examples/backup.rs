@@ -0,0 +1,15 @@
+use nxt::Nxt;
+use std::fs::File;
+
+#[tokio::main]
+async fn main() -> nxt::Result<()> {
+    let nxt = Nxt::first_usb().await?;
+
+    let path = format!("{}.tar", nxt.name());
+    println!("Backing up to {path}");
+
+    let manifest = nxt.backup(File::create(&path)?).await?;
+    print!("{manifest}");
+
+    Ok(())
+}
@@ -14,5 +14,12 @@ async fn main() -> nxt::Result<()> {
     let rendered = raster_to_string(&raster);
     println!("{rendered}");
 
+    // optionally save a screenshot, e.g. for a bug report
+    if let Some(path) = std::env::args().nth(1) {
+        let data = image::to_pbm(&raster, 4)?;
+        std::fs::write(&path, data)?;
+        println!("Saved screenshot to {path}");
+    }
+
     Ok(())
 }
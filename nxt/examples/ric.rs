@@ -0,0 +1,16 @@
+use nxt::{ric::Ric, system::raster_to_string};
+
+fn main() -> nxt::Result<()> {
+    let Some(path) = std::env::args().nth(1) else {
+        eprintln!("Usage: ric <file.ric>");
+        return Ok(());
+    };
+
+    let ric = Ric::parse(&std::fs::read(path)?)?;
+    println!("{ric:#?}");
+
+    let rendered = raster_to_string(&ric.to_raster());
+    println!("{rendered}");
+
+    Ok(())
+}
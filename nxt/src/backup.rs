@@ -0,0 +1,198 @@
+//! Snapshot all files on a brick into a tar archive and restore them
+
+use crate::{
+    protocol::DeviceError,
+    system::{DeviceInfo, FileInfo, FwVersion, ALL_FILES},
+    Error, Nxt, Result,
+};
+use std::{
+    fmt::{self, Display, Formatter},
+    io::{Read, Write},
+};
+
+/// Directory within the archive holding the brick's files
+const FILES_DIR: &str = "files";
+/// Path of the manifest within the archive
+const MANIFEST_PATH: &str = "manifest.txt";
+
+/// Description of the brick and its contents at the time of a backup.
+/// Stored in the archive as `manifest.txt`.
+#[derive(Debug)]
+pub struct Manifest {
+    /// Name of the brick
+    pub name: String,
+    /// Firmware and protocol versions
+    pub firmware: FwVersion,
+    /// General device information, including free flash
+    pub device_info: DeviceInfo,
+    /// Files included in the backup
+    pub files: Vec<FileInfo>,
+}
+
+impl Display for Manifest {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        let bt_addr = self
+            .device_info
+            .bt_addr
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect::<Vec<_>>()
+            .join(":");
+        let (s0, s1, s2, s3) = self.device_info.signal_strength;
+
+        writeln!(fmt, "name={}", self.name)?;
+        writeln!(
+            fmt,
+            "firmware={}.{}",
+            self.firmware.fw.0, self.firmware.fw.1
+        )?;
+        writeln!(
+            fmt,
+            "protocol={}.{}",
+            self.firmware.prot.0, self.firmware.prot.1
+        )?;
+        writeln!(fmt, "bt_addr={bt_addr}")?;
+        writeln!(fmt, "signal_strength={s0},{s1},{s2},{s3}")?;
+        writeln!(fmt, "flash={}", self.device_info.flash)?;
+        for file in &self.files {
+            writeln!(fmt, "file={} {}", file.name, file.len)?;
+        }
+        Ok(())
+    }
+}
+
+/// Append a file with the given contents to the archive
+fn append<W: Write>(
+    builder: &mut tar::Builder<W>,
+    path: &str,
+    data: &[u8],
+) -> Result<()> {
+    let mut header = tar::Header::new_gnu();
+    header.set_size(data.len().try_into()?);
+    header.set_mode(0o644);
+    header.set_cksum();
+    builder.append_data(&mut header, path, data)?;
+    Ok(())
+}
+
+/// Read the brick files out of a backup archive, ignoring the manifest
+fn read_archive<R: Read>(input: R) -> Result<Vec<(String, Vec<u8>)>> {
+    let mut archive = tar::Archive::new(input);
+    let mut files = Vec::new();
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let path = entry.path()?;
+        let Ok(name) = path.strip_prefix(FILES_DIR) else {
+            continue;
+        };
+        let name = name
+            .to_str()
+            .ok_or(Error::Parse("Invalid filename in archive"))?
+            .to_owned();
+        let mut data = Vec::new();
+        entry.read_to_end(&mut data)?;
+        files.push((name, data));
+    }
+    Ok(files)
+}
+
+impl Nxt {
+    /// Download every file on the brick and write them into a tar
+    /// archive, along with a [`Manifest`] describing the brick
+    pub async fn backup<W: Write>(&self, out: W) -> Result<Manifest> {
+        let firmware = self.get_firmware_version().await?;
+        let device_info = self.get_device_info().await?;
+        let files = self.list_files(ALL_FILES).await?;
+
+        let mut builder = tar::Builder::new(out);
+        for file in &files {
+            debug!("Backing up `{}` ({} bytes)", file.name, file.len);
+            let data = self.download_file(&file.name).await?;
+            let path = format!("{FILES_DIR}/{}", file.name);
+            append(&mut builder, &path, &data)?;
+        }
+
+        let manifest = Manifest {
+            name: device_info.name.clone(),
+            firmware,
+            device_info,
+            files,
+        };
+        let text = manifest.to_string();
+        append(&mut builder, MANIFEST_PATH, text.as_bytes())?;
+        builder.into_inner()?;
+
+        Ok(manifest)
+    }
+
+    /// Recreate the files from an archive produced by [`Nxt::backup`],
+    /// replacing any existing files of the same name. Each file is
+    /// written in the mode appropriate for its type (see
+    /// [`Nxt::upload_file`]). Returns the names of the restored files.
+    ///
+    /// The firmware can't rename files, so an existing file is deleted
+    /// before its replacement is uploaded; if the upload fails, the
+    /// brick's copy of that file is lost. Restoring again recovers it
+    /// from the archive.
+    pub async fn restore<R: Read>(&self, input: R) -> Result<Vec<String>> {
+        let files = read_archive(input)?;
+
+        for (name, data) in &files {
+            debug!("Restoring `{name}` ({} bytes)", data.len());
+            match self.file_delete(name).await {
+                Ok(()) | Err(Error::Device(DeviceError::FileNotFound)) => {}
+                Err(e) => return Err(e),
+            }
+            self.upload_file(name, data).await?;
+        }
+
+        Ok(files.into_iter().map(|(name, _)| name).collect())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        socket::mock::{self, Brick},
+        system::WriteMode,
+    };
+
+    fn brick() -> Brick {
+        let mut brick = Brick::default();
+        brick.add_file("prog.rxe", &[0xab; 300], WriteMode::Linear);
+        brick.add_file("log.rdt", b"1,2,3\n", WriteMode::Data);
+        brick.add_file("beep.rso", &[1, 2, 3, 4], WriteMode::Normal);
+        brick
+    }
+
+    #[tokio::test]
+    async fn backup_restore_roundtrip() {
+        let (nxt, state) = mock::connect(brick()).await;
+        let mut archive = Vec::new();
+        let manifest = nxt.backup(&mut archive).await.unwrap();
+        assert_eq!(manifest.name, "mock");
+        assert_eq!(manifest.files.len(), 3);
+
+        let original = std::mem::take(&mut state.lock().unwrap().files);
+        // one stale file which should be replaced
+        state
+            .lock()
+            .unwrap()
+            .add_file("beep.rso", &[9], WriteMode::Normal);
+
+        let mut restored = nxt.restore(archive.as_slice()).await.unwrap();
+        restored.sort();
+        assert_eq!(restored, ["beep.rso", "log.rdt", "prog.rxe"]);
+        assert_eq!(state.lock().unwrap().files, original);
+    }
+
+    #[tokio::test]
+    async fn manifest_contents() {
+        let (nxt, _state) = mock::connect(brick()).await;
+        let manifest = nxt.backup(std::io::sink()).await.unwrap();
+        let text = manifest.to_string();
+        assert!(text.starts_with("name=mock\nfirmware=1.31\n"));
+        assert!(text.contains("protocol=1.124\n"));
+        assert!(text.contains("file=prog.rxe 300\n"));
+    }
+}
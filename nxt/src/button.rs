@@ -0,0 +1,92 @@
+//! Simulating presses of the brick's buttons through the button
+//! module's iomap, to navigate the on-brick menus and dismiss dialogs
+//! from the host.
+//!
+//! The firmware only updates a button's state when the physical button
+//! changes, so injected events persist until they are consumed by the
+//! UI or a program, or the button is really pressed.
+
+pub use crate::iomap::button::Button;
+use crate::{iomap::button, Nxt, Result};
+use std::time::Duration;
+
+/// How long [`Nxt::click_button`] holds a button down: long enough for
+/// the firmware to see the press, short enough not to count as a long
+/// press
+pub const CLICK_DURATION: Duration = Duration::from_millis(100);
+
+impl Nxt {
+    /// Press the button and hold it down until [`Nxt::release_button`]
+    pub async fn press_button(&self, button: Button) -> Result<()> {
+        self.increment_button_counter(button::pressed_count(button))
+            .await?;
+        let state = self.read_field(button::state(button)).await?;
+        let state = state | button::STATE_PRESSED | button::STATE_PRESSED_EV;
+        self.write_field(button::state(button), &state).await
+    }
+
+    /// Release the button after a short press
+    pub async fn release_button(&self, button: Button) -> Result<()> {
+        self.increment_button_counter(button::short_release_count(button))
+            .await?;
+        self.increment_button_counter(button::release_count(button))
+            .await?;
+        let state = self.read_field(button::state(button)).await?;
+        let state =
+            (state & !button::STATE_PRESSED) | button::STATE_SHORT_RELEASED_EV;
+        self.write_field(button::state(button), &state).await
+    }
+
+    /// Press and release the button, holding it for [`CLICK_DURATION`].
+    /// Requires a Tokio runtime with the timer enabled.
+    pub async fn click_button(&self, button: Button) -> Result<()> {
+        self.press_button(button).await?;
+        tokio::time::sleep(CLICK_DURATION).await;
+        self.release_button(button).await
+    }
+
+    /// Add one to a button event counter, wrapping as the firmware does
+    async fn increment_button_counter(
+        &self,
+        field: crate::iomap::Field<u8>,
+    ) -> Result<()> {
+        let count = self.read_field(field).await?;
+        self.write_field(field, &count.wrapping_add(1)).await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::socket::mock::{self, Brick};
+
+    #[tokio::test]
+    async fn click() {
+        let mut brick = Brick::default();
+        brick.add_iomap(button::MODULE, 36);
+        let (nxt, state) = mock::connect(brick).await;
+        let iomap = || state.lock().unwrap().iomaps[&button::MODULE.id].clone();
+
+        nxt.press_button(Button::Enter).await.unwrap();
+        let map = iomap();
+        assert_eq!(map[24], 1);
+        assert_eq!(
+            map[32 + 3],
+            button::STATE_PRESSED | button::STATE_PRESSED_EV
+        );
+
+        nxt.release_button(Button::Enter).await.unwrap();
+        let map = iomap();
+        assert_eq!(map[24..29], [1, 0, 1, 0, 1]);
+        assert_eq!(
+            map[32 + 3],
+            button::STATE_PRESSED_EV | button::STATE_SHORT_RELEASED_EV
+        );
+
+        nxt.click_button(Button::Exit).await.unwrap();
+        let map = iomap();
+        assert_eq!(map[0..5], [1, 0, 1, 0, 1]);
+        assert_eq!(map[32], map[32 + 3]);
+        assert_eq!(map[8..24], [0; 16]);
+    }
+}
@@ -0,0 +1,180 @@
+//! Streaming output from programs on the brick through the poll
+//! buffers, like a serial console.
+//!
+//! Programs write to the USB or high speed poll buffer, which the host
+//! drains with [`Nxt::poll_command_length`] and [`Nxt::poll_command`].
+//! Writes may be split or merged across polls, so the data is
+//! reassembled into messages terminated by a newline. Data without
+//! newlines, such as binary telemetry, is passed on in messages of
+//! [`MAX_POLL_MESSAGE_LEN`] bytes.
+
+use crate::{
+    poll::{poll_stream, Poller},
+    system::BufType,
+    Nxt, Result,
+};
+use futures::Stream;
+use std::{borrow::Cow, collections::VecDeque, time::Duration};
+use tokio::time::Interval;
+
+/// Largest amount of data that can be read from a poll buffer in one
+/// request (packet size less the reply header, status, buffer and
+/// length)
+pub const POLL_CHUNK_SIZE: u8 = 64 - 5;
+
+/// Longest message yielded by [`Nxt::poll_messages`]; longer runs of
+/// data without a newline are split into messages of this length
+pub const MAX_POLL_MESSAGE_LEN: usize = 1024;
+
+/// A message read from a poll buffer
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PollMessage {
+    /// Buffer the message was read from
+    pub source: BufType,
+    /// Contents of the message, without the terminating newline (or
+    /// carriage return and newline), if it had one
+    pub data: Vec<u8>,
+}
+
+impl PollMessage {
+    /// The message as text, with any invalid UTF-8 replaced
+    #[must_use]
+    pub fn text(&self) -> Cow<'_, str> {
+        String::from_utf8_lossy(&self.data)
+    }
+}
+
+/// State carried between messages of [`Nxt::poll_messages`]
+struct Console {
+    /// Connection to the brick
+    nxt: Nxt,
+    /// Buffers to drain, each with the start of a message which has not
+    /// been terminated yet
+    buffers: Vec<(BufType, Vec<u8>)>,
+    /// Complete messages waiting to be yielded
+    ready: VecDeque<PollMessage>,
+}
+
+impl Poller for Console {
+    type Item = PollMessage;
+
+    /// Wait for the next complete message
+    async fn next(&mut self, interval: &mut Interval) -> Result<PollMessage> {
+        loop {
+            if let Some(message) = self.ready.pop_front() {
+                return Ok(message);
+            }
+            interval.tick().await;
+            for (source, partial) in &mut self.buffers {
+                drain(&self.nxt, *source, partial).await?;
+                while let Some(data) = split_message(partial) {
+                    self.ready.push_back(PollMessage {
+                        source: *source,
+                        data,
+                    });
+                }
+            }
+        }
+    }
+}
+
+/// Take the first message from the start of the data, if it has a
+/// newline or is long enough to be split
+fn split_message(partial: &mut Vec<u8>) -> Option<Vec<u8>> {
+    let limit = partial.len().min(MAX_POLL_MESSAGE_LEN);
+    if let Some(end) = partial[..limit].iter().position(|&b| b == b'\n') {
+        let mut data = partial.drain(..=end).collect::<Vec<_>>();
+        data.pop();
+        if data.last() == Some(&b'\r') {
+            data.pop();
+        }
+        Some(data)
+    } else if partial.len() >= MAX_POLL_MESSAGE_LEN {
+        Some(partial.drain(..MAX_POLL_MESSAGE_LEN).collect())
+    } else {
+        None
+    }
+}
+
+/// Read everything currently in a poll buffer onto the end of `out`
+async fn drain(nxt: &Nxt, buf: BufType, out: &mut Vec<u8>) -> Result<()> {
+    loop {
+        let len = nxt.poll_command_length(buf).await?;
+        if len == 0 {
+            return Ok(());
+        }
+        let data = nxt.poll_command(buf, len.min(POLL_CHUNK_SIZE)).await?;
+        if data.is_empty() {
+            return Ok(());
+        }
+        out.extend(data);
+    }
+}
+
+impl Nxt {
+    /// Poll the given buffers at the given interval, yielding each
+    /// newline-terminated message written to them by programs on the
+    /// brick. Each poll drains the buffers completely. Errors are
+    /// yielded without ending the stream; stop polling by dropping it.
+    ///
+    /// See [`MIN_POLL_INTERVAL`](crate::MIN_POLL_INTERVAL) for the
+    /// shortest interval and the runtime needed.
+    pub fn poll_messages(
+        &self,
+        buffers: &[BufType],
+        interval: Duration,
+    ) -> impl Stream<Item = Result<PollMessage>> + Send + 'static {
+        let console = Console {
+            nxt: self.clone(),
+            buffers: buffers.iter().map(|&buf| (buf, Vec::new())).collect(),
+            ready: VecDeque::new(),
+        };
+        poll_stream(interval, console)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::socket::mock::{self, Brick};
+    use futures::StreamExt;
+
+    #[tokio::test]
+    async fn messages() {
+        let (nxt, state) = mock::connect(Brick::default()).await;
+        let write = |buf: BufType, data: &[u8]| {
+            state.lock().unwrap().poll_bufs[buf as usize].extend(data);
+        };
+
+        let mut messages = Box::pin(nxt.poll_messages(
+            &[BufType::Usb, BufType::HighSpeed],
+            Duration::from_millis(1),
+        ));
+
+        write(BufType::Usb, b"hello\r\nwor");
+        write(BufType::HighSpeed, b"x=1\n");
+        let message = messages.next().await.unwrap().unwrap();
+        assert_eq!(message.source, BufType::Usb);
+        assert_eq!(message.text(), "hello");
+        let message = messages.next().await.unwrap().unwrap();
+        assert_eq!(message.source, BufType::HighSpeed);
+        assert_eq!(message.data, b"x=1");
+
+        // longer than one poll, and split across writes
+        let long = "d".repeat(100);
+        write(BufType::Usb, format!("ld\n{long}").as_bytes());
+        assert_eq!(messages.next().await.unwrap().unwrap().text(), "world");
+        write(BufType::Usb, b"\n");
+        assert_eq!(messages.next().await.unwrap().unwrap().text(), long);
+        assert!(state.lock().unwrap().poll_bufs[0].is_empty());
+
+        // data without newlines is split once it reaches the limit
+        let binary = vec![0xaa; MAX_POLL_MESSAGE_LEN + 10];
+        write(BufType::HighSpeed, &binary);
+        let message = messages.next().await.unwrap().unwrap();
+        assert_eq!(message.data, binary[..MAX_POLL_MESSAGE_LEN]);
+        write(BufType::HighSpeed, b"\n");
+        let message = messages.next().await.unwrap().unwrap();
+        assert_eq!(message.data, [0xaa; 10]);
+    }
+}
@@ -0,0 +1,224 @@
+//! Parsing of the `.log` and `.rdt` data log files written on the brick
+//! by NXT-G programs and the datalog blocks.
+//!
+//! Both are tab separated text. The first non-comment line is a header
+//! naming each column, with the unit in brackets, e.g.
+//! `Time (ms)\tLight (%)\tTemperature (C)`. Each following line holds
+//! one sample: the time in the first column and a reading for each
+//! sensor, which may be left empty if the sensor was not read.
+
+use crate::{Error, Nxt, Result};
+use std::{collections::BTreeMap, fmt::Write, time::Duration};
+
+/// A single reading from a sensor
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Sample {
+    /// Time since logging started
+    pub time: Duration,
+    /// Sensor reading, in the units of its series
+    pub value: f64,
+}
+
+/// All of the readings from one sensor
+#[derive(Clone, Debug, PartialEq)]
+pub struct Series {
+    /// Sensor name, as given in the log header
+    pub name: String,
+    /// Unit of the readings, if given in the log header
+    pub unit: Option<String>,
+    /// Readings, in the order they were logged
+    pub samples: Vec<Sample>,
+}
+
+/// A parsed data log file
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Datalog {
+    /// One time series per logged sensor
+    pub series: Vec<Series>,
+}
+
+/// Split a column header such as `Light (%)` into its name and unit
+fn split_header(col: &str) -> (String, Option<String>) {
+    let col = col.trim();
+    for (open, close) in [('(', ')'), ('[', ']')] {
+        if let Some(rest) = col.strip_suffix(close) {
+            if let Some((name, unit)) = rest.rsplit_once(open) {
+                return (
+                    name.trim().to_string(),
+                    Some(unit.trim().to_string()),
+                );
+            }
+        }
+    }
+    (col.to_string(), None)
+}
+
+/// Parse a time column value into a duration, given the unit from the
+/// header; times without a unit are taken to be milliseconds
+fn parse_time(value: &str, unit: Option<&str>) -> Result<Duration> {
+    let value = value
+        .trim()
+        .parse::<f64>()
+        .map_err(|_| Error::Parse("Invalid datalog time"))?;
+    let secs = match unit {
+        None | Some("ms") => value / 1000.0,
+        Some("s" | "sec") => value,
+        Some(_) => return Err(Error::Parse("Unknown datalog time unit")),
+    };
+    Duration::try_from_secs_f64(secs)
+        .map_err(|_| Error::Parse("Invalid datalog time"))
+}
+
+impl Datalog {
+    /// Parse the contents of a data log file
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        let text = std::str::from_utf8(data)
+            .map_err(|_| Error::Parse("Datalog is not text"))?;
+        let mut lines = text
+            .lines()
+            .map(str::trim_end)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'));
+
+        let header = lines.next().ok_or(Error::Parse("Datalog is empty"))?;
+        let mut cols = header.split('\t').map(split_header);
+        let (_, time_unit) =
+            cols.next().ok_or(Error::Parse("Datalog has no columns"))?;
+        let mut series = cols
+            .map(|(name, unit)| Series {
+                name,
+                unit,
+                samples: Vec::new(),
+            })
+            .collect::<Vec<_>>();
+
+        for line in lines {
+            let mut values = line.split('\t');
+            // `split` always yields at least one item
+            let time =
+                parse_time(values.next().unwrap(), time_unit.as_deref())?;
+            for (series, value) in series.iter_mut().zip(values) {
+                let value = value.trim();
+                if value.is_empty() {
+                    continue;
+                }
+                let value = value
+                    .parse()
+                    .map_err(|_| Error::Parse("Invalid datalog value"))?;
+                series.samples.push(Sample { time, value });
+            }
+        }
+
+        Ok(Self { series })
+    }
+
+    /// Look up the series for the named sensor
+    #[must_use]
+    pub fn series(&self, name: &str) -> Option<&Series> {
+        self.series.iter().find(|series| series.name == name)
+    }
+
+    /// Export the log as CSV, with one row per sample time and one column
+    /// per sensor. Times are in milliseconds; missing readings are left
+    /// empty.
+    #[must_use]
+    pub fn to_csv(&self) -> String {
+        let mut rows = BTreeMap::<Duration, Vec<Option<f64>>>::new();
+        for (idx, series) in self.series.iter().enumerate() {
+            for sample in &series.samples {
+                rows.entry(sample.time)
+                    .or_insert_with(|| vec![None; self.series.len()])[idx] =
+                    Some(sample.value);
+            }
+        }
+
+        let mut out = String::from("Time (ms)");
+        for series in &self.series {
+            let col = series.unit.as_ref().map_or_else(
+                || series.name.clone(),
+                |unit| format!("{} ({unit})", series.name),
+            );
+            out.push(',');
+            out.push_str(&csv_field(&col));
+        }
+        out.push('\n');
+        for (time, values) in rows {
+            // writing to a String cannot fail
+            write!(out, "{}", time.as_millis()).unwrap();
+            for value in values {
+                out.push(',');
+                if let Some(value) = value {
+                    write!(out, "{value}").unwrap();
+                }
+            }
+            out.push('\n');
+        }
+        out
+    }
+}
+
+/// Quote a CSV field if it contains any special characters
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+impl Nxt {
+    /// Download and parse the named data log file from the brick
+    pub async fn download_datalog(&self, name: &str) -> Result<Datalog> {
+        let data = self.download_file(name).await?;
+        Datalog::parse(&data)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const LOG: &str = "# Experiment 1\n\
+        Time (ms)\tLight (%)\tTemp, air [C]\tTouch\n\
+        0\t45\t21.5\t0\n\
+        100\t47\t\t1\n\
+        200\t52\t21.75\t\n";
+
+    #[test]
+    fn parse() {
+        let log = Datalog::parse(LOG.as_bytes()).unwrap();
+        assert_eq!(log.series.len(), 3);
+        let light = log.series("Light").unwrap();
+        assert_eq!(light.unit.as_deref(), Some("%"));
+        assert_eq!(
+            light.samples[2],
+            Sample {
+                time: Duration::from_millis(200),
+                value: 52.0,
+            }
+        );
+        let temp = log.series("Temp, air").unwrap();
+        assert_eq!(temp.unit.as_deref(), Some("C"));
+        assert_eq!(temp.samples.len(), 2);
+        let touch = log.series("Touch").unwrap();
+        assert_eq!(touch.unit, None);
+        assert_eq!(touch.samples.len(), 2);
+
+        Datalog::parse(b"").unwrap_err();
+        Datalog::parse(b"Time (s)\tLight\n0.5\tdark\n").unwrap_err();
+    }
+
+    #[test]
+    fn csv() {
+        let log = Datalog::parse(LOG.as_bytes()).unwrap();
+        assert_eq!(
+            log.to_csv(),
+            "Time (ms),Light (%),\"Temp, air (C)\",Touch\n\
+             0,45,21.5,0\n\
+             100,47,,1\n\
+             200,52,21.75,\n"
+        );
+
+        let secs = Datalog::parse(b"Time (s)\tA\n0.25\t1\n").unwrap();
+        assert_eq!(secs.to_csv(), "Time (ms),A\n250,1\n");
+    }
+}
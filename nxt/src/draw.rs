@@ -0,0 +1,441 @@
+//! Drawing onto a [`DisplayRaster`], for composing screens on the host
+//! before sending them to the brick with
+//! [`Nxt::draw_raster`](crate::Nxt::draw_raster).
+//!
+//! Coordinates are in pixels from the top-left corner of the screen;
+//! anything falling outside the screen is clipped. Pixel values are `1`
+//! for set (dark) and `0` for clear.
+
+use crate::{
+    font::{self, CHAR_HEIGHT},
+    system::DisplayRaster,
+    DISPLAY_HEIGHT, DISPLAY_WIDTH,
+};
+
+/// How source pixels are combined with the screen by [`Draw::blit`]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum BlitOp {
+    /// Replace the screen pixels
+    #[default]
+    Copy,
+    /// Keep screen pixels which are also set in the source
+    And,
+    /// Set screen pixels which are set in the source
+    Or,
+    /// Toggle screen pixels which are set in the source
+    Xor,
+}
+
+/// Drawing operations on a raster
+pub trait Draw {
+    /// Set every pixel to the given value
+    fn clear(&mut self, value: u8);
+
+    /// Read the pixel at the given position, or `None` if it is off
+    /// screen
+    fn get_pixel(&self, point: (i32, i32)) -> Option<u8>;
+
+    /// Set the pixel at the given position, if it is on screen
+    fn set_pixel(&mut self, point: (i32, i32), value: u8);
+
+    /// Draw a straight line between two points (inclusive)
+    fn line(&mut self, from: (i32, i32), to: (i32, i32), value: u8);
+
+    /// Draw a rectangle with its top-left corner at the given point
+    fn rect(
+        &mut self,
+        top_left: (i32, i32),
+        width: i32,
+        height: i32,
+        fill: bool,
+        value: u8,
+    );
+
+    /// Draw a circle centred on the given point
+    fn circle(
+        &mut self,
+        center: (i32, i32),
+        radius: i32,
+        fill: bool,
+        value: u8,
+    );
+
+    /// Draw text in the firmware font with the top-left corner of the
+    /// first character at the given point. Characters not present in the
+    /// font are skipped. Set pixels of each glyph are drawn with `value`
+    /// and the rest of the character cell with its inverse.
+    fn text(&mut self, top_left: (i32, i32), text: &str, value: u8);
+
+    /// Copy a row-major bitmap of the given width onto the screen with
+    /// its top-left corner at the given point
+    fn blit(
+        &mut self,
+        top_left: (i32, i32),
+        width: usize,
+        pixels: &[u8],
+        op: BlitOp,
+    );
+
+    /// Invert every pixel in the given rectangle
+    fn invert_rect(&mut self, top_left: (i32, i32), width: i32, height: i32);
+
+    /// Invert every pixel on the screen
+    fn invert(&mut self) {
+        self.invert_rect((0, 0), i32::MAX, i32::MAX);
+    }
+}
+
+impl Draw for DisplayRaster {
+    fn clear(&mut self, value: u8) {
+        for row in self.iter_mut() {
+            row.fill(value);
+        }
+    }
+
+    fn get_pixel(&self, (x, y): (i32, i32)) -> Option<u8> {
+        let (x, y) = (usize::try_from(x).ok()?, usize::try_from(y).ok()?);
+        self.get(y)?.get(x).copied()
+    }
+
+    fn set_pixel(&mut self, (x, y): (i32, i32), value: u8) {
+        if let (Ok(x), Ok(y)) = (usize::try_from(x), usize::try_from(y)) {
+            if x < DISPLAY_WIDTH && y < DISPLAY_HEIGHT {
+                self[y][x] = value;
+            }
+        }
+    }
+
+    fn line(&mut self, (x0, y0): (i32, i32), (x1, y1): (i32, i32), value: u8) {
+        // Bresenham's algorithm in closed form: step k of the m along the
+        // major axis moves floor((2kn + m) / 2m) of the n along the minor
+        // one. Only the steps to a visible row or column of the major
+        // axis are taken, so that distant points are cheap.
+        let dx = i128::from(x1) - i128::from(x0);
+        let dy = i128::from(y1) - i128::from(y0);
+        let y_major = dy.abs() > dx.abs();
+        let (p0, dp, q0, dq, size) = if y_major {
+            (y0, dy, x0, dx, DISPLAY_HEIGHT)
+        } else {
+            (x0, dx, y0, dy, DISPLAY_WIDTH)
+        };
+        let (p0, q0) = (i128::from(p0), i128::from(q0));
+        let (m, n) = (dp.abs(), dq.abs());
+        let last = i128::from(offset(0, size - 1));
+        let (first, end) = if dp < 0 {
+            (p0 - last, p0)
+        } else {
+            (-p0, last - p0)
+        };
+        for k in first.max(0)..=end.min(m) {
+            let minor = if m == 0 { 0 } else { (2 * k * n + m) / (2 * m) };
+            let p = i32::try_from(p0 + dp.signum() * k);
+            let q = i32::try_from(q0 + dq.signum() * minor);
+            let (Ok(p), Ok(q)) = (p, q) else {
+                continue;
+            };
+            self.set_pixel(if y_major { (q, p) } else { (p, q) }, value);
+        }
+    }
+
+    fn rect(
+        &mut self,
+        (x, y): (i32, i32),
+        width: i32,
+        height: i32,
+        fill: bool,
+        value: u8,
+    ) {
+        if width <= 0 || height <= 0 {
+            return;
+        }
+        // clip the corners to just outside the screen, so that edges
+        // off screen stay hidden without drawing all of their length
+        let (x1, y1) =
+            (x.saturating_add(width - 1), y.saturating_add(height - 1));
+        let (x, x1) = (clip(x, DISPLAY_WIDTH), clip(x1, DISPLAY_WIDTH));
+        let (y, y1) = (clip(y, DISPLAY_HEIGHT), clip(y1, DISPLAY_HEIGHT));
+        if fill {
+            for row in y..=y1 {
+                self.line((x, row), (x1, row), value);
+            }
+        } else {
+            self.line((x, y), (x1, y), value);
+            self.line((x, y1), (x1, y1), value);
+            self.line((x, y), (x, y1), value);
+            self.line((x1, y), (x1, y1), value);
+        }
+    }
+
+    fn circle(
+        &mut self,
+        (cx, cy): (i32, i32),
+        radius: i32,
+        fill: bool,
+        value: u8,
+    ) {
+        let Ok(r) = u64::try_from(radius) else {
+            return;
+        };
+        // midpoint circle algorithm, drawing all eight octants at once.
+        // Each step y draws the rows and columns y and x(y) away from the
+        // centre, so only the steps which reach the screen are taken:
+        // those to each visible row or column, and the widest step to
+        // each visible row at x(y) when filling.
+        let last = circle_last_step(r);
+        let rows = distances(cy, DISPLAY_HEIGHT);
+        let cols = distances(cx, DISPLAY_WIDTH);
+        let widest = rows
+            .clone()
+            .filter_map(|row| circle_widest_step(r, row))
+            .map(|step| step.min(last));
+        let steps = rows.chain(cols).chain(widest).filter(|&y| y <= last);
+        let at =
+            |dx: i32, dy: i32| (cx.saturating_add(dx), cy.saturating_add(dy));
+        for y in steps {
+            let (Ok(x), Ok(y)) =
+                (i32::try_from(circle_x(r, y)), i32::try_from(y))
+            else {
+                continue;
+            };
+            if fill {
+                self.line(at(-x, y), at(x, y), value);
+                self.line(at(-x, -y), at(x, -y), value);
+                self.line(at(-y, x), at(y, x), value);
+                self.line(at(-y, -x), at(y, -x), value);
+            } else {
+                for (px, py) in [
+                    (x, y),
+                    (y, x),
+                    (-y, x),
+                    (-x, y),
+                    (-x, -y),
+                    (-y, -x),
+                    (y, -x),
+                    (x, -y),
+                ] {
+                    self.set_pixel(at(px, py), value);
+                }
+            }
+        }
+    }
+
+    fn text(&mut self, (x, y): (i32, i32), text: &str, value: u8) {
+        let mut col = x;
+        for ch in text.chars() {
+            let Some(glyph) = font::glyph(ch) else {
+                continue;
+            };
+            for &bits in glyph {
+                for shift in 0..CHAR_HEIGHT {
+                    let on = (bits >> shift) & 0x01 != 0;
+                    let pen = if on { value } else { value ^ 0x01 };
+                    self.set_pixel((col, offset(y, shift)), pen);
+                }
+                col = col.saturating_add(1);
+            }
+        }
+    }
+
+    fn blit(
+        &mut self,
+        (x, y): (i32, i32),
+        width: usize,
+        pixels: &[u8],
+        op: BlitOp,
+    ) {
+        if width == 0 {
+            return;
+        }
+        for (i, src_row) in pixels.chunks(width).enumerate() {
+            let row = offset(y, i);
+            for (j, &src) in src_row.iter().enumerate() {
+                let col = offset(x, j);
+                let Some(current) = self.get_pixel((col, row)) else {
+                    continue;
+                };
+                let (current, src) = (current != 0, src != 0);
+                let value = match op {
+                    BlitOp::Copy => src,
+                    BlitOp::And => current && src,
+                    BlitOp::Or => current || src,
+                    BlitOp::Xor => current ^ src,
+                };
+                self.set_pixel((col, row), value.into());
+            }
+        }
+    }
+
+    fn invert_rect(&mut self, (x, y): (i32, i32), width: i32, height: i32) {
+        let cols = x.max(0)..x.saturating_add(width);
+        let rows = y.max(0)..y.saturating_add(height);
+        for row in rows.take(DISPLAY_HEIGHT) {
+            for col in cols.clone().take(DISPLAY_WIDTH) {
+                if let Some(current) = self.get_pixel((col, row)) {
+                    self.set_pixel((col, row), u8::from(current == 0));
+                }
+            }
+        }
+    }
+}
+
+/// Distances from a coordinate to each pixel along an axis of the screen
+fn distances(pos: i32, size: usize) -> impl Iterator<Item = u64> + Clone {
+    (0..size)
+        .map(move |i| (i64::from(offset(0, i)) - i64::from(pos)).unsigned_abs())
+}
+
+/// Offset from the centre in x of the point at step y of the midpoint
+/// circle algorithm: the largest x with x(x - 1) + y^2 < r^2. `y` must
+/// be at most the [last step](circle_last_step).
+const fn circle_x(r: u64, y: u64) -> u64 {
+    if y == 0 {
+        return r;
+    }
+    // (2x - 1)^2 <= 4(r^2 - y^2) - 3
+    (4 * (r * r - y * y) - 3).isqrt().div_ceil(2)
+}
+
+/// Last step of the midpoint circle algorithm, the largest y for which
+/// x(y) is at least y
+fn circle_last_step(r: u64) -> u64 {
+    // (4y - 1)^2 <= 8r^2 - 7
+    let step = ((8 * u128::from(r).pow(2)).saturating_sub(7).isqrt() + 1) / 4;
+    // at most r
+    u64::try_from(step).unwrap_or(r)
+}
+
+/// Last step of the midpoint circle algorithm, possibly beyond the last
+/// one taken, at which x(y) is at least `x`; `None` if there are none
+fn circle_widest_step(r: u64, x: u64) -> Option<u64> {
+    if x > r {
+        return None;
+    }
+    // y^2 <= r^2 - 1 - x(x - 1)
+    let rest = (r * r).checked_sub(1 + x * x.saturating_sub(1))?;
+    Some(rest.isqrt())
+}
+
+/// Clip a coordinate to at most one pixel beyond either edge of the
+/// screen, where it is still hidden
+fn clip(pos: i32, size: usize) -> i32 {
+    pos.clamp(-1, offset(0, size))
+}
+
+/// Offset a coordinate by a count of pixels, saturating far off screen
+fn offset(pos: i32, by: usize) -> i32 {
+    pos.saturating_add(i32::try_from(by).unwrap_or(i32::MAX))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Render the top-left corner of the raster for comparison
+    fn corner(raster: &DisplayRaster, width: usize, height: usize) -> String {
+        raster[..height]
+            .iter()
+            .map(|row| {
+                row[..width]
+                    .iter()
+                    .map(|&px| if px == 0 { '.' } else { '#' })
+                    .chain(std::iter::once('\n'))
+                    .collect::<String>()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn shapes() {
+        let mut raster = [[0; DISPLAY_WIDTH]; DISPLAY_HEIGHT];
+        raster.line((0, 0), (4, 2), 1);
+        raster.rect((5, 0), 3, 3, false, 1);
+        raster.circle((2, 6), 2, true, 1);
+        raster.set_pixel((-1, 0), 1);
+        raster.set_pixel((DISPLAY_WIDTH.try_into().unwrap(), 0), 1);
+        assert_eq!(
+            corner(&raster, 8, 9),
+            "#....###\n\
+             .##..#.#\n\
+             ...#####\n\
+             ........\n\
+             .###....\n\
+             #####...\n\
+             #####...\n\
+             #####...\n\
+             .###....\n"
+        );
+        assert_eq!(raster.get_pixel((-1, 0)), None);
+    }
+
+    #[test]
+    fn extreme_coordinates() {
+        let mut raster = [[0; DISPLAY_WIDTH]; DISPLAY_HEIGHT];
+        // only the top and bottom edges are on screen
+        raster.rect((-10, 1), i32::MAX, 3, false, 1);
+        assert_eq!(corner(&raster, 3, 4), "...\n###\n...\n###\n");
+        assert_eq!(raster[3][DISPLAY_WIDTH - 1], 1);
+        raster.rect((i32::MIN, i32::MIN), i32::MAX, i32::MAX, true, 1);
+
+        let far = i32::MAX - 1;
+        raster.line((far, i32::MIN), (i32::MAX, i32::MIN + 2), 1);
+        raster.circle((far, far), 3, true, 1);
+        raster.circle((i32::MIN, i32::MIN), 3, false, 1);
+        raster.text((far, far), "ab", 1);
+        raster.blit((far, far), 2, &[1; 4], BlitOp::Copy);
+        assert_eq!(raster.iter().flatten().filter(|&&p| p != 0).count(), 200);
+
+        // only the parts of long lines and huge circles on screen are
+        // walked
+        let mut raster = [[0; DISPLAY_WIDTH]; DISPLAY_HEIGHT];
+        raster.line((0, 0), (i32::MAX, 0), 1);
+        raster.line((i32::MIN, i32::MIN), (i32::MAX, i32::MAX), 1);
+        assert_eq!(raster[0], [1; DISPLAY_WIDTH]);
+        raster.circle((0, i32::MAX), i32::MAX - 10, false, 1);
+        assert_eq!(raster[10][0], 1);
+        raster.circle((50, 32), i32::MAX, true, 1);
+        assert!(raster.iter().flatten().all(|&p| p == 1));
+    }
+
+    #[test]
+    fn text_and_invert() {
+        let mut raster = [[0; DISPLAY_WIDTH]; DISPLAY_HEIGHT];
+        raster.text((0, 0), "T", 1);
+        assert_eq!(
+            corner(&raster, 6, 8),
+            "#####.\n\
+             ..#...\n\
+             ..#...\n\
+             ..#...\n\
+             ..#...\n\
+             ..#...\n\
+             ..#...\n\
+             ......\n"
+        );
+        raster.invert_rect((0, 0), 3, 2);
+        assert_eq!(corner(&raster, 6, 2), "...##.\n##....\n");
+        raster.invert();
+        assert_eq!(raster[63][99], 1);
+        assert_eq!(corner(&raster, 6, 2), "###..#\n..####\n");
+    }
+
+    #[test]
+    fn blit_ops() {
+        let src = [1, 1, 0, 0];
+        for (op, expected) in [
+            (BlitOp::Copy, "##..\n"),
+            (BlitOp::And, "#...\n"),
+            (BlitOp::Or, "###.\n"),
+            (BlitOp::Xor, ".##.\n"),
+        ] {
+            let mut raster = [[0; DISPLAY_WIDTH]; DISPLAY_HEIGHT];
+            raster.blit((0, 0), 4, &[1, 0, 1, 0], BlitOp::Copy);
+            raster.blit((0, 0), 4, &src, op);
+            assert_eq!(corner(&raster, 4, 1), expected, "{op:?}");
+        }
+
+        // clipped at the edge of the screen
+        let mut raster = [[0; DISPLAY_WIDTH]; DISPLAY_HEIGHT];
+        raster.blit((98, 63), 2, &[1, 1, 1, 1], BlitOp::Copy);
+        assert_eq!(raster[63][98..], [1, 1]);
+    }
+}
@@ -17,6 +17,14 @@ pub enum Error {
     #[error("bluetooth error")]
     Bluetooth(#[from] bluer::Error),
 
+    #[cfg(feature = "png")]
+    #[error("PNG decoding error")]
+    PngDecode(#[from] png::DecodingError),
+
+    #[cfg(feature = "png")]
+    #[error("PNG encoding error")]
+    PngEncode(#[from] png::EncodingError),
+
     #[error("device error")]
     Device(#[from] crate::protocol::DeviceError),
 
@@ -38,6 +46,30 @@ pub enum Error {
     #[error("Invalid charactors for string")]
     InvalidString(#[from] std::string::FromUtf8Error),
 
+    #[error("Not enough flash: need {required} bytes, {available} free")]
+    InsufficientFlash { required: u32, available: u32 },
+
+    #[error("Verification failed in chunk {chunk} (offset {offset})")]
+    VerifyMismatch { chunk: usize, offset: usize },
+
+    #[error("Flash controller error: {0}")]
+    Flash(&'static str),
+
+    #[error(
+        "Inbox {0} is out of range (0 to {max})",
+        max = crate::MAX_INBOX_ID
+    )]
+    InvalidInbox(u8),
+
+    #[error(
+        "Message of {0} bytes is too long (max {max} bytes)",
+        max = crate::MAX_MESSAGE_LEN
+    )]
+    MessageTooLong(usize),
+
+    #[error("No reply after {0} attempts")]
+    NoReply(u32),
+
     #[error("Integer out of range for type")]
     IntOutOfRange(#[from] std::num::TryFromIntError),
 }
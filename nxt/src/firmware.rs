@@ -0,0 +1,266 @@
+//! Parsing and validation of NXT firmware images (`.rfw` files).
+//!
+//! A firmware image is a raw copy of the brick's flash, starting with
+//! the ARM exception vectors. The image carries no header, so its
+//! flavour and version are identified from the strings it contains.
+
+use crate::{system::FwVersion, Error, Nxt, Result};
+use std::{
+    cmp::Ordering,
+    fmt::{self, Display, Formatter},
+};
+
+/// Address at which the flash is mapped
+pub const FLASH_BASE: u32 = 0x0010_0000;
+/// Size of the flash in the NXT's AT91SAM7S256 microcontroller
+pub const FLASH_SIZE: usize = 256 * 1024;
+/// Size of a flash page, the unit in which flash is written
+pub const FLASH_PAGE_SIZE: usize = 256;
+/// Number of pages covered by each lock bit
+pub const FLASH_LOCK_REGION_PAGES: usize = 64;
+
+/// Family of firmware an image belongs to
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Flavour {
+    /// The standard LEGO firmware
+    Standard,
+    /// John Hansen's enhanced firmware for NBC/NXC
+    Enhanced,
+    /// leJOS NXJ
+    LeJos,
+    /// Not recognised
+    Unknown,
+}
+
+impl Display for Flavour {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        fmt.write_str(match self {
+            Self::Standard => "LEGO",
+            Self::Enhanced => "NBC/NXC enhanced",
+            Self::LeJos => "leJOS",
+            Self::Unknown => "unknown",
+        })
+    }
+}
+
+/// How an image's version compares with the firmware running on a brick
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum VersionCheck {
+    /// The brick already runs this version
+    Same,
+    /// The image is newer than the brick's firmware
+    Newer,
+    /// The image is older than the brick's firmware
+    Older,
+    /// The image's version could not be identified
+    Unknown,
+}
+
+/// A validated firmware image
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Firmware {
+    /// Raw image data
+    pub data: Vec<u8>,
+    /// Identified firmware family
+    pub flavour: Flavour,
+    /// Firmware version as `(major, minor)`, if identified
+    pub version: Option<(u8, u8)>,
+}
+
+/// Find the first occurrence of `needle` in `haystack`
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+/// Identify the firmware family from marker strings in the image
+fn identify_flavour(data: &[u8]) -> Flavour {
+    if find(data, b"leJOS").is_some() {
+        Flavour::LeJos
+    } else if find(data, b"NBC/NXC").is_some() {
+        Flavour::Enhanced
+    } else if find(data, b"LEGO").is_some() {
+        Flavour::Standard
+    } else {
+        Flavour::Unknown
+    }
+}
+
+/// Look for a version string of the form `FW 1.31`. Shorter forms
+/// such as `V1.31` aren't accepted, as they are too easily matched by
+/// chance in the binary image.
+fn identify_version(data: &[u8]) -> Option<(u8, u8)> {
+    const PREFIX: &[u8] = b"FW ";
+    let digit = |byte: u8| byte.is_ascii_digit().then(|| byte - b'0');
+    let mut start = 0;
+    while let Some(pos) = find(&data[start..], PREFIX) {
+        let at = start + pos + PREFIX.len();
+        if let Some(&[major, b'.', tens, units]) = data.get(at..at + 4) {
+            if let (Some(major), Some(tens), Some(units)) =
+                (digit(major), digit(tens), digit(units))
+            {
+                return Some((major, tens * 10 + units));
+            }
+        }
+        start = at;
+    }
+    None
+}
+
+/// Check that the word is an ARM branch or `ldr pc, [pc, #n]`, as
+/// found in the reset vector
+const fn is_reset_vector(word: u32) -> bool {
+    word & 0xff00_0000 == 0xea00_0000 || word & 0xffff_f000 == 0xe59f_f000
+}
+
+impl Firmware {
+    /// Parse and validate a firmware image
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        if data.len() < 4 {
+            return Err(Error::Parse("Firmware image too short"));
+        }
+        if data.len() > FLASH_SIZE {
+            return Err(Error::Parse("Firmware image larger than flash"));
+        }
+        let reset = u32::from_le_bytes([data[0], data[1], data[2], data[3]]);
+        if !is_reset_vector(reset) {
+            return Err(Error::Parse("Firmware image has no reset vector"));
+        }
+
+        Ok(Self {
+            data: data.to_vec(),
+            flavour: identify_flavour(data),
+            version: identify_version(data),
+        })
+    }
+
+    /// Number of flash pages occupied by the image
+    #[must_use]
+    pub const fn page_count(&self) -> usize {
+        self.data.len().div_ceil(FLASH_PAGE_SIZE)
+    }
+
+    /// Iterate over the image in whole flash pages, padding the last
+    /// page with `0xff` (erased flash)
+    pub fn pages(&self) -> impl Iterator<Item = [u8; FLASH_PAGE_SIZE]> + '_ {
+        self.data.chunks(FLASH_PAGE_SIZE).map(|chunk| {
+            let mut page = [0xff; FLASH_PAGE_SIZE];
+            page[..chunk.len()].copy_from_slice(chunk);
+            page
+        })
+    }
+
+    /// Compare the image's version with the firmware running on a brick
+    #[must_use]
+    pub fn compare(&self, running: &FwVersion) -> VersionCheck {
+        match self.version.map(|version| version.cmp(&running.fw)) {
+            Some(Ordering::Equal) => VersionCheck::Same,
+            Some(Ordering::Greater) => VersionCheck::Newer,
+            Some(Ordering::Less) => VersionCheck::Older,
+            None => VersionCheck::Unknown,
+        }
+    }
+}
+
+impl Display for Firmware {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        write!(fmt, "{} firmware", self.flavour)?;
+        if let Some((major, minor)) = self.version {
+            write!(fmt, " {major}.{minor:02}")?;
+        }
+        write!(
+            fmt,
+            ", {} bytes ({} of {} pages)",
+            self.data.len(),
+            self.page_count(),
+            FLASH_SIZE / FLASH_PAGE_SIZE
+        )
+    }
+}
+
+impl Nxt {
+    /// Compare a firmware image against the firmware running on this
+    /// brick
+    pub async fn check_firmware(
+        &self,
+        firmware: &Firmware,
+    ) -> Result<VersionCheck> {
+        let running = self.get_firmware_version().await?;
+        Ok(firmware.compare(&running))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::socket::mock::{self, Brick};
+
+    /// Build an image with a reset vector followed by the given strings
+    fn image(strings: &[&[u8]]) -> Vec<u8> {
+        let mut data = 0xea00_0006u32.to_le_bytes().to_vec();
+        data.resize(0x40, 0);
+        for string in strings {
+            data.extend(*string);
+            data.push(0);
+        }
+        data
+    }
+
+    #[test]
+    fn identify() {
+        let fw = Firmware::parse(&image(&[b"LEGO", b"V", b"FW 1.31"])).unwrap();
+        assert_eq!(fw.flavour, Flavour::Standard);
+        assert_eq!(fw.version, Some((1, 31)));
+        assert_eq!(fw.page_count(), 1);
+        let page = fw.pages().next().unwrap();
+        assert_eq!(&page[..4], [0x06, 0, 0, 0xea]);
+        assert_eq!(page[FLASH_PAGE_SIZE - 1], 0xff);
+        assert_eq!(
+            fw.to_string(),
+            "LEGO firmware 1.31, 79 bytes (1 of 1024 pages)"
+        );
+
+        let fw = Firmware::parse(&image(&[b"LEGO NBC/NXC FW 1.28"])).unwrap();
+        assert_eq!(fw.flavour, Flavour::Enhanced);
+        assert_eq!(fw.version, Some((1, 28)));
+
+        // a stray `V` followed by digits isn't a version
+        let fw = Firmware::parse(&image(&[b"LEGO", b"V1.28"])).unwrap();
+        assert_eq!(fw.version, None);
+
+        let fw = Firmware::parse(&image(&[])).unwrap();
+        assert_eq!(fw.flavour, Flavour::Unknown);
+        assert_eq!(fw.version, None);
+    }
+
+    #[test]
+    fn invalid() {
+        Firmware::parse(&[0; 64]).unwrap_err();
+        let mut huge = image(&[]);
+        huge.resize(FLASH_SIZE + 1, 0);
+        Firmware::parse(&huge).unwrap_err();
+    }
+
+    #[tokio::test]
+    async fn compare_with_brick() {
+        let (nxt, _) = mock::connect(Brick::default()).await;
+        let check = |data: &[u8]| Firmware::parse(&image(&[data])).unwrap();
+        assert_eq!(
+            nxt.check_firmware(&check(b"FW 1.31")).await.unwrap(),
+            VersionCheck::Same
+        );
+        assert_eq!(
+            nxt.check_firmware(&check(b"FW 1.29")).await.unwrap(),
+            VersionCheck::Older
+        );
+        assert_eq!(
+            nxt.check_firmware(&check(b"FW 2.00")).await.unwrap(),
+            VersionCheck::Newer
+        );
+        assert_eq!(
+            nxt.check_firmware(&check(b"")).await.unwrap(),
+            VersionCheck::Unknown
+        );
+    }
+}
@@ -0,0 +1,242 @@
+//! Reporting on the use of the brick's user flash storage
+
+use crate::{
+    system::{FileInfo, FileType, ALL_FILES},
+    Nxt, Result,
+};
+use std::fmt::{self, Display, Formatter};
+
+/// Size of a flash sector; files occupy a whole number of sectors
+pub const FLASH_SECTOR_SIZE: u32 = 256;
+
+/// Round the provided length up to a whole number of flash sectors,
+/// saturating at `u32::MAX` for lengths which can't be rounded up
+#[must_use]
+pub const fn sector_align(len: u32) -> u32 {
+    match checked_sector_align(len) {
+        Some(aligned) => aligned,
+        None => u32::MAX,
+    }
+}
+
+/// Round the provided length up to a whole number of flash sectors, or
+/// `None` if the result doesn't fit in a `u32`
+const fn checked_sector_align(len: u32) -> Option<u32> {
+    len.div_ceil(FLASH_SECTOR_SIZE)
+        .checked_mul(FLASH_SECTOR_SIZE)
+}
+
+/// Flash used by a single file
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FileUsage {
+    /// Name of the file
+    pub name: String,
+    /// Length of the file, in bytes
+    pub len: u32,
+    /// Type of the file, based on its extension
+    pub file_type: FileType,
+    /// Flash occupied by the file, rounded up to whole sectors
+    pub used: u32,
+}
+
+impl From<FileInfo> for FileUsage {
+    fn from(info: FileInfo) -> Self {
+        Self {
+            file_type: info.file_type(),
+            used: sector_align(info.len),
+            name: info.name,
+            len: info.len,
+        }
+    }
+}
+
+/// Summary of the files stored on the brick and the remaining free
+/// flash, as returned by [`Nxt::flash_report`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FlashReport {
+    /// Files on the brick, largest first
+    pub files: Vec<FileUsage>,
+    /// Free flash reported by the brick, in bytes
+    pub free: u32,
+}
+
+impl FlashReport {
+    /// Build a report from a file listing and the free flash reported
+    /// by [`Nxt::get_device_info`]
+    #[must_use]
+    pub fn new(files: Vec<FileInfo>, free: u32) -> Self {
+        let mut files =
+            files.into_iter().map(FileUsage::from).collect::<Vec<_>>();
+        files.sort_by(|a, b| b.used.cmp(&a.used).then(a.name.cmp(&b.name)));
+        Self { files, free }
+    }
+
+    /// Total flash occupied by files, in bytes
+    #[must_use]
+    pub fn used(&self) -> u32 {
+        self.files.iter().map(|file| file.used).sum()
+    }
+
+    /// Whether a linear file (e.g. an executable) of the given length
+    /// will fit in the free flash.
+    ///
+    /// Linear files must occupy contiguous sectors. Fragmentation is not
+    /// visible over the protocol, so this compares against the total
+    /// free space and a `NoLinearSpace` error is still possible if the
+    /// free sectors are scattered.
+    #[must_use]
+    pub const fn fits_linear(&self, len: u32) -> bool {
+        match checked_sector_align(len) {
+            Some(needed) => needed <= self.free,
+            None => false,
+        }
+    }
+
+    /// Suggest a set of files to delete so that a linear file of the
+    /// given length will fit. Returns an empty list if it already
+    /// fits, or `None` if it cannot fit even with every file removed.
+    ///
+    /// A single file is preferred if one is large enough, picking the
+    /// smallest such file; otherwise the largest files are chosen
+    /// first to keep the number of deletions low. System files
+    /// (`.sys`), which hold firmware settings, are never suggested.
+    #[must_use]
+    pub fn suggest_deletions(&self, len: u32) -> Option<Vec<&FileUsage>> {
+        let needed = checked_sector_align(len)?;
+        if needed <= self.free {
+            return Some(Vec::new());
+        }
+        let shortfall = needed - self.free;
+
+        let candidates = self
+            .files
+            .iter()
+            .filter(|file| !is_system_file(&file.name))
+            .collect::<Vec<_>>();
+
+        // files are sorted largest first, so the last match is the
+        // smallest file which is big enough on its own
+        if let Some(file) =
+            candidates.iter().rev().find(|file| file.used >= shortfall)
+        {
+            return Some(vec![file]);
+        }
+
+        let mut freed: u32 = 0;
+        let mut chosen = Vec::new();
+        for file in candidates {
+            freed = freed.saturating_add(file.used);
+            chosen.push(file);
+            if freed >= shortfall {
+                return Some(chosen);
+            }
+        }
+        None
+    }
+}
+
+/// Whether the named file is a firmware system file
+fn is_system_file(name: &str) -> bool {
+    name.to_ascii_lowercase().ends_with(".sys")
+}
+
+impl Display for FlashReport {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        for file in &self.files {
+            writeln!(
+                fmt,
+                "{:<20} {:>7} {:>7}  {:?}",
+                file.name, file.len, file.used, file.file_type
+            )?;
+        }
+        writeln!(fmt, "Used: {} bytes", self.used())?;
+        writeln!(fmt, "Free: {} bytes", self.free)
+    }
+}
+
+impl Nxt {
+    /// Combine the file listing with the free flash to report how the
+    /// brick's storage is being used
+    pub async fn flash_report(&self) -> Result<FlashReport> {
+        let files = self.list_files(ALL_FILES).await?;
+        let free = self.get_device_info().await?.flash;
+        Ok(FlashReport::new(files, free))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        socket::mock::{self, Brick},
+        system::WriteMode,
+    };
+
+    fn file(name: &str, len: u32) -> FileInfo {
+        FileInfo {
+            name: name.into(),
+            len,
+        }
+    }
+
+    fn report() -> FlashReport {
+        FlashReport::new(
+            vec![
+                file("small.rso", 100),
+                file("NVConfig.sys", 4000),
+                file("big.rxe", 3000),
+                file("mid.rdt", 1000),
+            ],
+            512,
+        )
+    }
+
+    fn names(files: &[&FileUsage]) -> Vec<String> {
+        files.iter().map(|file| file.name.clone()).collect()
+    }
+
+    #[test]
+    fn usage() {
+        let report = report();
+        assert_eq!(report.files[0].name, "NVConfig.sys");
+        assert_eq!(report.files[3].used, 256);
+        assert_eq!(report.used(), 4096 + 3072 + 1024 + 256);
+        assert!(report.fits_linear(512));
+        assert!(!report.fits_linear(513));
+        assert_eq!(sector_align(u32::MAX), u32::MAX);
+        assert!(!report.fits_linear(u32::MAX));
+        assert_eq!(report.suggest_deletions(u32::MAX - 1), None);
+    }
+
+    #[test]
+    fn deletions() {
+        let report = report();
+        assert_eq!(report.suggest_deletions(300), Some(Vec::new()));
+        // smallest single file which frees enough
+        let del = report.suggest_deletions(1200).unwrap();
+        assert_eq!(names(&del), ["mid.rdt"]);
+        // needs several files, but never the system file
+        let del = report.suggest_deletions(4500).unwrap();
+        assert_eq!(names(&del), ["big.rxe", "mid.rdt"]);
+        assert_eq!(report.suggest_deletions(6000), None);
+    }
+
+    #[tokio::test]
+    async fn report_from_brick() {
+        let mut brick = Brick::default();
+        brick.flash = 1000;
+        brick.add_file("prog.rxe", &[0; 10], WriteMode::Linear);
+        let (nxt, _state) = mock::connect(brick).await;
+        let report = nxt.flash_report().await.unwrap();
+        assert_eq!(report.free, 1000);
+        assert_eq!(
+            report.files,
+            [FileUsage {
+                name: "prog.rxe".into(),
+                len: 10,
+                file_type: FileType::Executable,
+                used: 256,
+            }]
+        );
+    }
+}
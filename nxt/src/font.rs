@@ -0,0 +1,335 @@
+//! The 5x8 font used by the NXT firmware to draw text on the LCD, and
+//! recognition of text drawn in it
+
+use crate::{system::DisplayRaster, DISPLAY_HEIGHT, DISPLAY_WIDTH};
+use std::collections::HashMap;
+
+/// Width of a character cell in pixels, including spacing
+pub const CHAR_WIDTH: usize = 6;
+/// Height of a character cell in pixels
+pub const CHAR_HEIGHT: usize = 8;
+/// First character included in the font
+const FIRST_CHAR: char = ' ';
+/// Last character included in the font
+const LAST_CHAR: char = '~';
+
+/// Glyph data for the printable ASCII characters, in the same
+/// column-major format as the display memory: one byte per column,
+/// least significant bit at the top.
+const GLYPHS: [[u8; CHAR_WIDTH]; 95] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // ' '
+    [0x00, 0x00, 0x5f, 0x00, 0x00, 0x00], // '!'
+    [0x00, 0x07, 0x00, 0x07, 0x00, 0x00], // '"'
+    [0x14, 0x7f, 0x14, 0x7f, 0x14, 0x00], // '#'
+    [0x24, 0x2a, 0x7f, 0x2a, 0x12, 0x00], // '$'
+    [0x23, 0x13, 0x08, 0x64, 0x62, 0x00], // '%'
+    [0x36, 0x49, 0x55, 0x22, 0x50, 0x00], // '&'
+    [0x00, 0x00, 0x07, 0x00, 0x00, 0x00], // '''
+    [0x00, 0x1c, 0x22, 0x41, 0x00, 0x00], // '('
+    [0x00, 0x41, 0x22, 0x1c, 0x00, 0x00], // ')'
+    [0x2a, 0x1c, 0x7f, 0x1c, 0x2a, 0x00], // '*'
+    [0x08, 0x08, 0x3e, 0x08, 0x08, 0x00], // '+'
+    [0x00, 0x50, 0x30, 0x00, 0x00, 0x00], // ','
+    [0x08, 0x08, 0x08, 0x08, 0x08, 0x00], // '-'
+    [0x00, 0x60, 0x60, 0x00, 0x00, 0x00], // '.'
+    [0x20, 0x10, 0x08, 0x04, 0x02, 0x00], // '/'
+    [0x3e, 0x51, 0x49, 0x45, 0x3e, 0x00], // '0'
+    [0x00, 0x42, 0x7f, 0x40, 0x00, 0x00], // '1'
+    [0x42, 0x61, 0x51, 0x49, 0x46, 0x00], // '2'
+    [0x21, 0x41, 0x45, 0x4b, 0x31, 0x00], // '3'
+    [0x18, 0x14, 0x12, 0x7f, 0x10, 0x00], // '4'
+    [0x27, 0x45, 0x45, 0x45, 0x39, 0x00], // '5'
+    [0x3c, 0x4a, 0x49, 0x49, 0x30, 0x00], // '6'
+    [0x01, 0x71, 0x09, 0x05, 0x03, 0x00], // '7'
+    [0x36, 0x49, 0x49, 0x49, 0x36, 0x00], // '8'
+    [0x06, 0x49, 0x49, 0x29, 0x1e, 0x00], // '9'
+    [0x00, 0x36, 0x36, 0x00, 0x00, 0x00], // ':'
+    [0x00, 0x56, 0x36, 0x00, 0x00, 0x00], // ';'
+    [0x08, 0x14, 0x22, 0x41, 0x00, 0x00], // '<'
+    [0x14, 0x14, 0x14, 0x14, 0x14, 0x00], // '='
+    [0x00, 0x41, 0x22, 0x14, 0x08, 0x00], // '>'
+    [0x02, 0x01, 0x51, 0x09, 0x06, 0x00], // '?'
+    [0x32, 0x49, 0x79, 0x41, 0x3e, 0x00], // '@'
+    [0x7e, 0x11, 0x11, 0x11, 0x7e, 0x00], // 'A'
+    [0x7f, 0x49, 0x49, 0x49, 0x36, 0x00], // 'B'
+    [0x3e, 0x41, 0x41, 0x41, 0x22, 0x00], // 'C'
+    [0x7f, 0x41, 0x41, 0x22, 0x1c, 0x00], // 'D'
+    [0x7f, 0x49, 0x49, 0x49, 0x41, 0x00], // 'E'
+    [0x7f, 0x09, 0x09, 0x09, 0x01, 0x00], // 'F'
+    [0x3e, 0x41, 0x49, 0x49, 0x7a, 0x00], // 'G'
+    [0x7f, 0x08, 0x08, 0x08, 0x7f, 0x00], // 'H'
+    [0x00, 0x41, 0x7f, 0x41, 0x00, 0x00], // 'I'
+    [0x20, 0x40, 0x41, 0x3f, 0x01, 0x00], // 'J'
+    [0x7f, 0x08, 0x14, 0x22, 0x41, 0x00], // 'K'
+    [0x7f, 0x40, 0x40, 0x40, 0x40, 0x00], // 'L'
+    [0x7f, 0x02, 0x04, 0x02, 0x7f, 0x00], // 'M'
+    [0x7f, 0x04, 0x08, 0x10, 0x7f, 0x00], // 'N'
+    [0x3e, 0x41, 0x41, 0x41, 0x3e, 0x00], // 'O'
+    [0x7f, 0x09, 0x09, 0x09, 0x06, 0x00], // 'P'
+    [0x3e, 0x41, 0x51, 0x21, 0x5e, 0x00], // 'Q'
+    [0x7f, 0x09, 0x19, 0x29, 0x46, 0x00], // 'R'
+    [0x46, 0x49, 0x49, 0x49, 0x31, 0x00], // 'S'
+    [0x01, 0x01, 0x7f, 0x01, 0x01, 0x00], // 'T'
+    [0x3f, 0x40, 0x40, 0x40, 0x3f, 0x00], // 'U'
+    [0x1f, 0x20, 0x40, 0x20, 0x1f, 0x00], // 'V'
+    [0x3f, 0x40, 0x38, 0x40, 0x3f, 0x00], // 'W'
+    [0x63, 0x14, 0x08, 0x14, 0x63, 0x00], // 'X'
+    [0x07, 0x08, 0x70, 0x08, 0x07, 0x00], // 'Y'
+    [0x61, 0x51, 0x49, 0x45, 0x43, 0x00], // 'Z'
+    [0x00, 0x7f, 0x41, 0x41, 0x00, 0x00], // '['
+    [0x02, 0x04, 0x08, 0x10, 0x20, 0x00], // '\'
+    [0x00, 0x41, 0x41, 0x7f, 0x00, 0x00], // ']'
+    [0x04, 0x02, 0x01, 0x02, 0x04, 0x00], // '^'
+    [0x40, 0x40, 0x40, 0x40, 0x40, 0x00], // '_'
+    [0x00, 0x01, 0x02, 0x04, 0x00, 0x00], // '`'
+    [0x20, 0x54, 0x54, 0x54, 0x78, 0x00], // 'a'
+    [0x7f, 0x48, 0x44, 0x44, 0x38, 0x00], // 'b'
+    [0x38, 0x44, 0x44, 0x44, 0x20, 0x00], // 'c'
+    [0x38, 0x44, 0x44, 0x48, 0x7f, 0x00], // 'd'
+    [0x38, 0x54, 0x54, 0x54, 0x08, 0x00], // 'e'
+    [0x08, 0x7e, 0x09, 0x01, 0x02, 0x00], // 'f'
+    [0x18, 0xa4, 0xa4, 0xa4, 0x7c, 0x00], // 'g'
+    [0x7f, 0x08, 0x04, 0x04, 0x78, 0x00], // 'h'
+    [0x00, 0x00, 0x7d, 0x40, 0x00, 0x00], // 'i'
+    [0x40, 0x80, 0x84, 0x7d, 0x00, 0x00], // 'j'
+    [0x7f, 0x10, 0x28, 0x44, 0x00, 0x00], // 'k'
+    [0x00, 0x00, 0x7f, 0x40, 0x00, 0x00], // 'l'
+    [0x7c, 0x04, 0x18, 0x04, 0x78, 0x00], // 'm'
+    [0x7c, 0x08, 0x04, 0x04, 0x78, 0x00], // 'n'
+    [0x38, 0x44, 0x44, 0x44, 0x38, 0x00], // 'o'
+    [0xfc, 0x24, 0x24, 0x24, 0x18, 0x00], // 'p'
+    [0x18, 0x24, 0x24, 0x18, 0xfc, 0x00], // 'q'
+    [0x7c, 0x08, 0x04, 0x04, 0x08, 0x00], // 'r'
+    [0x08, 0x54, 0x54, 0x54, 0x20, 0x00], // 's'
+    [0x04, 0x3e, 0x44, 0x24, 0x00, 0x00], // 't'
+    [0x3c, 0x40, 0x40, 0x20, 0x7c, 0x00], // 'u'
+    [0x1c, 0x20, 0x40, 0x20, 0x1c, 0x00], // 'v'
+    [0x3c, 0x40, 0x30, 0x40, 0x3c, 0x00], // 'w'
+    [0x44, 0x28, 0x10, 0x28, 0x44, 0x00], // 'x'
+    [0x9c, 0xa0, 0x60, 0x3c, 0x00, 0x00], // 'y'
+    [0x44, 0x64, 0x54, 0x4c, 0x44, 0x00], // 'z'
+    [0x00, 0x08, 0x36, 0x41, 0x00, 0x00], // '{'
+    [0x00, 0x00, 0x7f, 0x00, 0x00, 0x00], // '|'
+    [0x00, 0x41, 0x36, 0x08, 0x00, 0x00], // '}'
+    [0x08, 0x04, 0x08, 0x10, 0x08, 0x00], // '~'
+];
+
+/// Look up the glyph for the given character, or `None` if it is not
+/// included in the font
+#[must_use]
+pub fn glyph(ch: char) -> Option<&'static [u8; CHAR_WIDTH]> {
+    if (FIRST_CHAR..=LAST_CHAR).contains(&ch) {
+        Some(&GLYPHS[ch as usize - FIRST_CHAR as usize])
+    } else {
+        None
+    }
+}
+
+/// Iterate over every character in the font along with its glyph
+pub fn glyphs() -> impl Iterator<Item = (char, &'static [u8; CHAR_WIDTH])> {
+    (FIRST_CHAR..=LAST_CHAR).zip(GLYPHS.iter())
+}
+
+/// A run of text found on the screen by [`read_text`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TextRun {
+    /// The recognised text. Blank character cells between glyphs are
+    /// included as spaces.
+    pub text: String,
+    /// Column of the left edge of the first character
+    pub x: usize,
+    /// Row of the top edge of the characters
+    pub y: usize,
+    /// Whether the text is drawn light on dark, as for the selected item
+    /// of a menu
+    pub inverted: bool,
+}
+
+impl TextRun {
+    /// Width of the run in pixels
+    #[must_use]
+    pub fn width(&self) -> usize {
+        self.text.chars().count() * CHAR_WIDTH
+    }
+
+    /// Whether this run and another cover any of the same pixels
+    fn overlaps(&self, other: &Self) -> bool {
+        self.x < other.x + other.width()
+            && other.x < self.x + self.width()
+            && self.y < other.y + CHAR_HEIGHT
+            && other.y < self.y + CHAR_HEIGHT
+    }
+}
+
+/// Find text drawn in this font on the screen, at any position.
+///
+/// Glyphs must match exactly, including the blank column to their
+/// right, so text overlapping other graphics is not found. Runs are
+/// returned in reading order. Where candidate runs overlap, as may
+/// happen when graphics resemble a glyph, the longest is kept.
+#[must_use]
+pub fn read_text(raster: &DisplayRaster) -> Vec<TextRun> {
+    let lookup = glyphs()
+        .filter(|&(ch, _)| ch != ' ')
+        .map(|(ch, glyph)| (*glyph, ch))
+        .collect::<HashMap<_, _>>();
+
+    let mut candidates = Vec::new();
+    for y in 0..=DISPLAY_HEIGHT - CHAR_HEIGHT {
+        // the columns of the strip of cells with their top at this row,
+        // in the same format as the glyphs
+        let cols = (0..DISPLAY_WIDTH)
+            .map(|x| {
+                (0..CHAR_HEIGHT).fold(0u8, |bits, row| {
+                    bits | u8::from(raster[y + row][x] != 0) << row
+                })
+            })
+            .collect::<Vec<_>>();
+        for inverted in [false, true] {
+            let cols = cols
+                .iter()
+                .map(|&bits| if inverted { !bits } else { bits })
+                .collect::<Vec<_>>();
+            find_runs(&lookup, &cols, y, inverted, &mut candidates);
+        }
+    }
+
+    candidates.sort_by_key(|run| std::cmp::Reverse(run.text.len()));
+    let mut runs: Vec<TextRun> = Vec::new();
+    for run in candidates {
+        if !runs.iter().any(|other| other.overlaps(&run)) {
+            runs.push(run);
+        }
+    }
+    runs.sort_by_key(|run| (run.y, run.x));
+    runs
+}
+
+/// Whether the text appears on the screen within a single run, see
+/// [`read_text`]
+#[must_use]
+pub fn screen_contains(raster: &DisplayRaster, text: &str) -> bool {
+    read_text(raster).iter().any(|run| run.text.contains(text))
+}
+
+/// Find runs of glyphs along one strip of the screen, scanning from left
+/// to right
+fn find_runs(
+    lookup: &HashMap<[u8; CHAR_WIDTH], char>,
+    cols: &[u8],
+    y: usize,
+    inverted: bool,
+    out: &mut Vec<TextRun>,
+) {
+    let mut run: Option<TextRun> = None;
+    let mut x = 0;
+    while let Some(cell) = cols.get(x..x + CHAR_WIDTH) {
+        // the slice is exactly one cell wide
+        let Some(&ch) =
+            lookup.get::<[u8; CHAR_WIDTH]>(cell.try_into().unwrap())
+        else {
+            x += 1;
+            continue;
+        };
+        match &mut run {
+            // continue the run across whole blank cells
+            Some(current)
+                if (x - current.x) % CHAR_WIDTH == 0
+                    && cols[current.x + current.width()..x]
+                        .iter()
+                        .all(|&bits| bits == 0) =>
+            {
+                let spaces =
+                    (x - current.x) / CHAR_WIDTH - current.text.chars().count();
+                current.text.extend(std::iter::repeat_n(' ', spaces));
+                current.text.push(ch);
+            }
+            _ => {
+                out.extend(run.take());
+                run = Some(TextRun {
+                    text: ch.into(),
+                    x,
+                    y,
+                    inverted,
+                });
+            }
+        }
+        x += CHAR_WIDTH;
+    }
+    out.extend(run);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn glyphs_are_distinct() {
+        let unique = glyphs().map(|(_, glyph)| glyph).collect::<HashSet<_>>();
+        assert_eq!(unique.len(), GLYPHS.len());
+        assert_eq!(glyph('M'), Some(&[0x7f, 0x02, 0x04, 0x02, 0x7f, 0x00]));
+        assert_eq!(glyph('\n'), None);
+    }
+
+    #[test]
+    fn matches_brick_screen() {
+        // "My Files" as read from the display of a brick (see the
+        // display raster test in `system`)
+        const MY_FILES: [u8; 48] = [
+            127, 2, 4, 2, 127, 0, 156, 160, 96, 60, 0, 0, 0, 0, 0, 0, 0, 0,
+            127, 9, 9, 9, 1, 0, 0, 0, 125, 64, 0, 0, 0, 0, 127, 64, 0, 0, 56,
+            84, 84, 84, 8, 0, 8, 84, 84, 84, 32, 0,
+        ];
+        let rendered = "My Files"
+            .chars()
+            .flat_map(|ch| *glyph(ch).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(rendered, MY_FILES);
+    }
+
+    #[test]
+    fn recognise_text() {
+        use crate::draw::Draw;
+
+        let mut raster = [[0; DISPLAY_WIDTH]; DISPLAY_HEIGHT];
+        raster.text((3, 2), "Hello  world!", 1);
+        raster.rect((0, 20), 100, 10, true, 1);
+        raster.text((20, 21), "Run", 0);
+        raster.text((60, 21), "Stop", 0);
+        raster.circle((50, 50), 8, false, 1);
+        raster.text((70, 50), "x", 1);
+
+        assert_eq!(
+            read_text(&raster),
+            [
+                TextRun {
+                    text: "Hello  world!".into(),
+                    x: 3,
+                    y: 2,
+                    inverted: false,
+                },
+                TextRun {
+                    text: "Run".into(),
+                    x: 20,
+                    y: 21,
+                    inverted: true,
+                },
+                TextRun {
+                    text: "Stop".into(),
+                    x: 60,
+                    y: 21,
+                    inverted: true,
+                },
+                TextRun {
+                    text: "x".into(),
+                    x: 70,
+                    y: 50,
+                    inverted: false,
+                },
+            ]
+        );
+        assert!(screen_contains(&raster, "world"));
+        assert!(!screen_contains(&raster, "Run Stop"));
+    }
+}
@@ -0,0 +1,338 @@
+//! Conversion between a [`DisplayRaster`] and image files, for
+//! screenshots of the brick's display.
+//!
+//! Rasters can be exported as binary PBM, or as PNG with the `png`
+//! feature. Both can be scaled up by an integer factor, and PNG exports
+//! can use colours resembling the real LCD. Importing accepts
+//! monochrome images of the display size or a whole multiple of it, as
+//! produced by the exports.
+
+use crate::{
+    system::DisplayRaster, Error, Result, DISPLAY_HEIGHT, DISPLAY_WIDTH,
+};
+
+/// Largest scale factor for exports, which is already far bigger than
+/// any screen
+pub const MAX_SCALE: usize = 64;
+
+/// Options for exporting a raster as an image
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Style {
+    /// Width and height of each display pixel in the image, from 1 to
+    /// [`MAX_SCALE`]
+    pub scale: usize,
+    /// Colour of set pixels, as RGB
+    pub set: [u8; 3],
+    /// Colour of clear pixels, as RGB
+    pub clear: [u8; 3],
+}
+
+impl Style {
+    /// Black on white, one image pixel per display pixel
+    pub const MONO: Self = Self {
+        scale: 1,
+        set: [0x00, 0x00, 0x00],
+        clear: [0xff, 0xff, 0xff],
+    };
+
+    /// Dark grey on the grey-green of the NXT's LCD, scaled up to make
+    /// individual pixels visible
+    pub const LCD: Self = Self {
+        scale: 4,
+        set: [0x2b, 0x33, 0x2b],
+        clear: [0xb4, 0xc3, 0xa4],
+    };
+
+    /// Change the scale factor
+    #[must_use]
+    pub const fn with_scale(self, scale: usize) -> Self {
+        Self { scale, ..self }
+    }
+
+    /// Size of the exported image in pixels
+    const fn size(&self) -> Result<(usize, usize)> {
+        if self.scale == 0 {
+            return Err(Error::Serialise("Image scale must be at least 1"));
+        }
+        if self.scale > MAX_SCALE {
+            return Err(Error::Serialise("Image scale too large"));
+        }
+        Ok((DISPLAY_WIDTH * self.scale, DISPLAY_HEIGHT * self.scale))
+    }
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Self::MONO
+    }
+}
+
+/// Scale the raster up into row-major image pixels, one byte each
+fn scaled_pixels(raster: &DisplayRaster, scale: usize) -> Vec<u8> {
+    raster
+        .iter()
+        .flat_map(|row| {
+            let row = row
+                .iter()
+                .flat_map(|&px| std::iter::repeat_n(px, scale))
+                .collect::<Vec<_>>();
+            std::iter::repeat_n(row, scale).flatten()
+        })
+        .collect()
+}
+
+/// Export the raster as a binary (`P4`) PBM image, scaled up by the
+/// given factor, at most [`MAX_SCALE`]. PBM has no colour, so set pixels
+/// are black.
+pub fn to_pbm(raster: &DisplayRaster, scale: usize) -> Result<Vec<u8>> {
+    let (width, height) = Style::MONO.with_scale(scale).size()?;
+    let mut out = format!("P4\n{width} {height}\n").into_bytes();
+    let pixels = scaled_pixels(raster, scale);
+    for row in pixels.chunks(width) {
+        // rows are padded to a whole number of bytes, MSB first
+        out.extend(row.chunks(8).map(|bits| {
+            bits.iter()
+                .zip((0..8).rev())
+                .fold(0u8, |byte, (&px, shift)| {
+                    byte | (u8::from(px != 0) << shift)
+                })
+        }));
+    }
+    Ok(out)
+}
+
+/// Import a plain (`P1`) or binary (`P4`) PBM image
+pub fn from_pbm(data: &[u8]) -> Result<DisplayRaster> {
+    let mut pos = 0;
+    let magic = pbm_token(data, &mut pos)?;
+    let width = pbm_number(data, &mut pos)?;
+    let height = pbm_number(data, &mut pos)?;
+    // check before the size is used to index the data
+    check_size(width, height)?;
+    let pixels = match magic {
+        b"P1" => {
+            let pixels = data[pos..]
+                .iter()
+                .filter(|b| !b.is_ascii_whitespace())
+                .map(|b| match b {
+                    b'0' => Ok(0),
+                    b'1' => Ok(1),
+                    _ => Err(Error::Parse("Invalid PBM pixel")),
+                })
+                .take(width * height)
+                .collect::<Result<Vec<u8>>>()?;
+            if pixels.len() != width * height {
+                return Err(Error::Parse("PBM image data too short"));
+            }
+            pixels
+        }
+        b"P4" => {
+            // a single whitespace character separates header and data
+            let data = data.get(pos + 1..).unwrap_or_default();
+            let stride = width.div_ceil(8);
+            if data.len() < stride * height {
+                return Err(Error::Parse("PBM image data too short"));
+            }
+            data.chunks(stride)
+                .take(height)
+                .flat_map(|row| {
+                    (0..width).map(|x| (row[x / 8] >> (7 - x % 8)) & 0x01)
+                })
+                .collect()
+        }
+        _ => return Err(Error::Parse("Not a PBM image")),
+    };
+    from_pixels(width, height, &pixels)
+}
+
+/// Read the next whitespace-separated header token of a PBM image,
+/// skipping comments
+fn pbm_token<'a>(data: &'a [u8], pos: &mut usize) -> Result<&'a [u8]> {
+    loop {
+        match data.get(*pos) {
+            Some(b'#') => {
+                while data.get(*pos).is_some_and(|&b| b != b'\n') {
+                    *pos += 1;
+                }
+            }
+            Some(b) if b.is_ascii_whitespace() => *pos += 1,
+            Some(_) => break,
+            None => return Err(Error::Parse("Truncated PBM header")),
+        }
+    }
+    let start = *pos;
+    while data.get(*pos).is_some_and(|b| !b.is_ascii_whitespace()) {
+        *pos += 1;
+    }
+    Ok(&data[start..*pos])
+}
+
+/// Read a number from the header of a PBM image
+fn pbm_number(data: &[u8], pos: &mut usize) -> Result<usize> {
+    std::str::from_utf8(pbm_token(data, pos)?)
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .ok_or(Error::Parse("Invalid PBM header"))
+}
+
+/// Export the raster as a PNG image in the given style
+#[cfg(feature = "png")]
+pub fn to_png(raster: &DisplayRaster, style: &Style) -> Result<Vec<u8>> {
+    let (width, height) = style.size()?;
+    let mut out = Vec::new();
+    let mut encoder =
+        png::Encoder::new(&mut out, width.try_into()?, height.try_into()?);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    let data = scaled_pixels(raster, style.scale)
+        .into_iter()
+        .flat_map(|px| if px == 0 { style.clear } else { style.set })
+        .collect::<Vec<u8>>();
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&data)?;
+    writer.finish()?;
+    Ok(out)
+}
+
+/// Import a PNG image. Dark, opaque pixels are set.
+#[cfg(feature = "png")]
+pub fn from_png(data: &[u8]) -> Result<DisplayRaster> {
+    let (width, height, pixels) = decode_png(data)?;
+    from_pixels(width, height, &pixels)
+}
+
+/// Check that an image is the size of the display scaled up by a whole
+/// number, returning the scale
+const fn check_size(width: usize, height: usize) -> Result<usize> {
+    let scale = width / DISPLAY_WIDTH;
+    if scale == 0
+        || width != DISPLAY_WIDTH * scale
+        || height != DISPLAY_HEIGHT * scale
+        || width.checked_mul(height).is_none()
+    {
+        return Err(Error::Parse("Image size does not match the display"));
+    }
+    Ok(scale)
+}
+
+/// Build a raster from row-major pixels, scaling down images which are
+/// a whole multiple of the display size
+fn from_pixels(
+    width: usize,
+    height: usize,
+    pixels: &[u8],
+) -> Result<DisplayRaster> {
+    let scale = check_size(width, height)?;
+    let mut raster = [[0; DISPLAY_WIDTH]; DISPLAY_HEIGHT];
+    for (y, row) in raster.iter_mut().enumerate() {
+        for (x, px) in row.iter_mut().enumerate() {
+            // sample the middle of each scaled pixel
+            let (sx, sy) = (x * scale + scale / 2, y * scale + scale / 2);
+            *px = u8::from(pixels[sy * width + sx] != 0);
+        }
+    }
+    Ok(raster)
+}
+
+/// Decode a PNG into row-major pixels, one byte per pixel, set where
+/// the image is dark and opaque
+#[cfg(feature = "png")]
+pub(crate) fn decode_png(data: &[u8]) -> Result<(usize, usize, Vec<u8>)> {
+    let mut decoder = png::Decoder::new(data);
+    decoder.set_transformations(
+        png::Transformations::EXPAND | png::Transformations::STRIP_16,
+    );
+    let mut reader = decoder.read_info()?;
+    let mut buf = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buf)?;
+    let (width, height) = (info.width as usize, info.height as usize);
+
+    let channels = info.color_type.samples();
+    let pixels = buf[..info.buffer_size()]
+        .chunks_exact(channels)
+        .map(|px| {
+            let (luma, alpha) = match *px {
+                [l] => (u32::from(l), 255),
+                [l, a] => (u32::from(l), a),
+                [r, g, b] => (luminance(r, g, b), 255),
+                [r, g, b, a] => (luminance(r, g, b), a),
+                _ => unreachable!("PNG pixels have 1-4 channels"),
+            };
+            u8::from(alpha >= 0x80 && luma < 0x80)
+        })
+        .collect::<Vec<_>>();
+
+    Ok((width, height, pixels))
+}
+
+/// Approximate perceived brightness of an RGB colour
+#[cfg(feature = "png")]
+fn luminance(r: u8, g: u8, b: u8) -> u32 {
+    (u32::from(r) * 299 + u32::from(g) * 587 + u32::from(b) * 114) / 1000
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::draw::Draw;
+
+    /// A raster with some recognisable content
+    fn sample() -> DisplayRaster {
+        let mut raster = [[0; DISPLAY_WIDTH]; DISPLAY_HEIGHT];
+        raster.text((1, 1), "NXT", 1);
+        raster.circle((70, 40), 15, false, 1);
+        raster.set_pixel((99, 63), 1);
+        raster
+    }
+
+    #[test]
+    fn pbm() {
+        let raster = sample();
+        let data = to_pbm(&raster, 1).unwrap();
+        assert!(data.starts_with(b"P4\n100 64\n"));
+        assert_eq!(data.len(), 10 + 13 * 64);
+        assert_eq!(from_pbm(&data).unwrap(), raster);
+
+        let data = to_pbm(&raster, 3).unwrap();
+        assert!(data.starts_with(b"P4\n300 192\n"));
+        assert_eq!(from_pbm(&data).unwrap(), raster);
+        to_pbm(&raster, 0).unwrap_err();
+        to_pbm(&raster, MAX_SCALE + 1).unwrap_err();
+        to_pbm(&raster, 1_000_000).unwrap_err();
+
+        // plain format with comments
+        let mut plain = b"P1\n# screenshot\n100 64\n".to_vec();
+        for row in &raster {
+            for &px in row {
+                plain.push(b'0' + px);
+                plain.push(b' ');
+            }
+            plain.push(b'\n');
+        }
+        assert_eq!(from_pbm(&plain).unwrap(), raster);
+
+        from_pbm(b"P4\n99 64\n").unwrap_err();
+        from_pbm(b"P4\n100 64\n\0\0").unwrap_err();
+        from_pbm(b"P5\n100 64\n").unwrap_err();
+        from_pbm(b"P4\n0 64\n").unwrap_err();
+        from_pbm(b"P4\n0 0\n").unwrap_err();
+        // a whole multiple of the display size, too big to address
+        let scale = usize::MAX / (DISPLAY_WIDTH * DISPLAY_HEIGHT) + 1;
+        let (width, height) = (DISPLAY_WIDTH * scale, DISPLAY_HEIGHT * scale);
+        for magic in ["P1", "P4"] {
+            let huge = format!("{magic}\n{width} {height}\n");
+            from_pbm(huge.as_bytes()).unwrap_err();
+        }
+    }
+
+    #[cfg(feature = "png")]
+    #[test]
+    fn png() {
+        let raster = sample();
+        for style in [Style::MONO, Style::LCD, Style::LCD.with_scale(1)] {
+            let data = to_png(&raster, &style).unwrap();
+            assert_eq!(from_png(&data).unwrap(), raster, "{style:?}");
+        }
+        to_png(&raster, &Style::LCD.with_scale(MAX_SCALE + 1)).unwrap_err();
+    }
+}
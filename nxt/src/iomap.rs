@@ -0,0 +1,338 @@
+//! Typed descriptions of the iomaps exposed by each firmware module.
+//!
+//! Every module of the NXT firmware publishes a struct (its iomap) which
+//! can be read and written with [`Nxt::read_io_map`] and
+//! [`Nxt::write_io_map`]. The submodules here describe the layout of
+//! each of those structs as [`Field`]s, which can be read and written by
+//! name with [`Nxt::read_field`] and [`Nxt::write_field`]. Offsets are
+//! taken from the `.iom` headers of the LEGO firmware source, and are
+//! shared by the NBC/NXC enhanced firmware.
+
+use crate::{
+    motor::{RegulationMode, RunState},
+    sensor::{SensorMode, SensorType},
+    Error, Nxt, Result,
+};
+use std::{
+    fmt::{self, Debug, Formatter},
+    marker::PhantomData,
+};
+
+pub mod button;
+pub mod comm;
+pub mod command;
+pub mod display;
+pub mod input;
+pub mod ioctrl;
+pub mod loader;
+pub mod lowspeed;
+pub mod output;
+pub mod sound;
+pub mod ui;
+
+/// Largest amount of iomap data that can be read in one request
+/// (packet size less the reply header, status, module ID and length)
+pub const IOMAP_READ_CHUNK_SIZE: u16 = 64 - 9;
+/// Largest amount of iomap data that can be written in one request
+/// (packet size less the request header, module ID, offset and length)
+pub const IOMAP_WRITE_CHUNK_SIZE: u16 = 64 - 10;
+
+/// A firmware module which exposes an iomap
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Module {
+    /// Name of the module, as returned by the module search APIs
+    pub name: &'static str,
+    /// ID of the module in the LEGO firmware. Other firmware may differ,
+    /// so [`Nxt::read_field`] and [`Nxt::write_field`] look the ID up by
+    /// name instead.
+    pub id: u32,
+}
+
+/// Every module with an iomap described in this crate
+pub const MODULES: [Module; 11] = [
+    command::MODULE,
+    output::MODULE,
+    input::MODULE,
+    button::MODULE,
+    comm::MODULE,
+    ioctrl::MODULE,
+    sound::MODULE,
+    loader::MODULE,
+    display::MODULE,
+    lowspeed::MODULE,
+    ui::MODULE,
+];
+
+/// A field within a module's iomap, holding a value of type `T`
+pub struct Field<T> {
+    /// Module the field belongs to
+    pub module: Module,
+    /// Offset of the field within the iomap
+    pub offset: u16,
+    /// Length of the field, in bytes
+    pub len: u16,
+    /// Type of the field's value
+    value: PhantomData<fn() -> T>,
+}
+
+impl<T> Field<T> {
+    /// Describe a field at the given offset within the module's iomap
+    #[must_use]
+    pub const fn new(module: Module, offset: u16, len: u16) -> Self {
+        Self {
+            module,
+            offset,
+            len,
+            value: PhantomData,
+        }
+    }
+}
+
+impl<T> Clone for Field<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Field<T> {}
+
+impl<T> Debug for Field<T> {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        fmt.debug_struct("Field")
+            .field("module", &self.module.name)
+            .field("offset", &self.offset)
+            .field("len", &self.len)
+            .finish()
+    }
+}
+
+/// Conversion between a Rust value and its representation in an iomap
+pub trait FieldValue: Sized + Send + Sync {
+    /// Decode the raw contents of a field
+    fn decode(data: &[u8]) -> Result<Self>;
+
+    /// Encode the value into a field of the given length
+    fn encode(&self, len: usize) -> Result<Vec<u8>>;
+}
+
+/// Implement [`FieldValue`] for little-endian integer types
+macro_rules! int_field {
+    ($($ty:ty),*) => {$(
+        impl FieldValue for $ty {
+            fn decode(data: &[u8]) -> Result<Self> {
+                let bytes = data
+                    .get(..std::mem::size_of::<Self>())
+                    .ok_or(Error::Parse("Iomap field too short"))?;
+                // length checked above
+                Ok(Self::from_le_bytes(bytes.try_into().unwrap()))
+            }
+
+            fn encode(&self, len: usize) -> Result<Vec<u8>> {
+                if len == std::mem::size_of::<Self>() {
+                    Ok(self.to_le_bytes().to_vec())
+                } else {
+                    Err(Error::Serialise("Iomap field length mismatch"))
+                }
+            }
+        }
+    )*};
+}
+
+int_field!(u8, i8, u16, i16, u32, i32);
+
+/// Implement [`FieldValue`] for single-byte enums with a `TryFrom<u8>`
+/// implementation
+macro_rules! enum_field {
+    ($($ty:ty),*) => {$(
+        impl $crate::iomap::FieldValue for $ty {
+            fn decode(data: &[u8]) -> $crate::Result<Self> {
+                <u8 as $crate::iomap::FieldValue>::decode(data)?.try_into()
+            }
+
+            fn encode(&self, len: usize) -> $crate::Result<Vec<u8>> {
+                $crate::iomap::FieldValue::encode(&(*self as u8), len)
+            }
+        }
+    )*};
+}
+pub(crate) use enum_field;
+
+enum_field!(RunState, RegulationMode, SensorType, SensorMode);
+
+impl FieldValue for bool {
+    fn decode(data: &[u8]) -> Result<Self> {
+        Ok(u8::decode(data)? != 0)
+    }
+
+    fn encode(&self, len: usize) -> Result<Vec<u8>> {
+        u8::from(*self).encode(len)
+    }
+}
+
+/// Null-terminated strings
+impl FieldValue for String {
+    fn decode(data: &[u8]) -> Result<Self> {
+        let end = data.iter().position(|&b| b == 0).unwrap_or(data.len());
+        Ok(Self::from_utf8(data[..end].to_vec())?)
+    }
+
+    fn encode(&self, len: usize) -> Result<Vec<u8>> {
+        if self.len() >= len {
+            return Err(Error::Serialise("String too long for iomap field"));
+        }
+        let mut out = self.as_bytes().to_vec();
+        out.resize(len, 0);
+        Ok(out)
+    }
+}
+
+/// Raw bytes, which must fill the field exactly
+impl FieldValue for Vec<u8> {
+    fn decode(data: &[u8]) -> Result<Self> {
+        Ok(data.to_vec())
+    }
+
+    fn encode(&self, len: usize) -> Result<Vec<u8>> {
+        if self.len() == len {
+            Ok(self.clone())
+        } else {
+            Err(Error::Serialise("Iomap field length mismatch"))
+        }
+    }
+}
+
+/// Offset of an entry in an array of structs within an iomap. The index
+/// types of the submodules keep `idx` within the array.
+const fn entry_offset(base: u16, stride: u16, idx: u8) -> u16 {
+    base + stride * idx as u16
+}
+
+impl Nxt {
+    /// Read `len` bytes from a module's iomap, split into as many
+    /// requests as necessary
+    pub(crate) async fn read_io_map_range(
+        &self,
+        mod_id: u32,
+        offset: u16,
+        len: u16,
+    ) -> Result<Vec<u8>> {
+        let mut out = Vec::with_capacity(len.into());
+        let mut pos = 0;
+        while pos < len {
+            let count = (len - pos).min(IOMAP_READ_CHUNK_SIZE);
+            let chunk = self.read_io_map(mod_id, offset + pos, count).await?;
+            if chunk.len() != usize::from(count) {
+                return Err(Error::Parse("Short iomap read"));
+            }
+            out.extend(chunk);
+            pos += count;
+        }
+        Ok(out)
+    }
+
+    /// Write data into a module's iomap, split into as many requests as
+    /// necessary
+    pub(crate) async fn write_io_map_range(
+        &self,
+        mod_id: u32,
+        offset: u16,
+        data: &[u8],
+    ) -> Result<()> {
+        for (chunk, pos) in data
+            .chunks(IOMAP_WRITE_CHUNK_SIZE.into())
+            .zip((offset..).step_by(IOMAP_WRITE_CHUNK_SIZE.into()))
+        {
+            let written = self.write_io_map(mod_id, pos, chunk).await?;
+            if usize::from(written) != chunk.len() {
+                return Err(Error::Write);
+            }
+        }
+        Ok(())
+    }
+
+    /// Read and decode the value of an iomap field. The module's ID is
+    /// looked up by name, see [`Nxt::module_id`].
+    pub async fn read_field<T: FieldValue>(
+        &self,
+        field: Field<T>,
+    ) -> Result<T> {
+        let id = self.module_id(field.module.name).await?;
+        let data = self.read_io_map_range(id, field.offset, field.len).await?;
+        T::decode(&data)
+    }
+
+    /// Encode and write a value into an iomap field. The module's ID is
+    /// looked up by name, see [`Nxt::module_id`].
+    pub async fn write_field<T: FieldValue>(
+        &self,
+        field: Field<T>,
+        value: &T,
+    ) -> Result<()> {
+        let data = value.encode(field.len.into())?;
+        let id = self.module_id(field.module.name).await?;
+        self.write_io_map_range(id, field.offset, &data).await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        motor::OutPort,
+        socket::mock::{self, Brick},
+    };
+
+    #[test]
+    fn encode_decode() {
+        assert_eq!((-2i16).encode(2).unwrap(), [0xfe, 0xff]);
+        assert_eq!(u32::decode(&[1, 2, 3, 4]).unwrap(), 0x0403_0201);
+        u16::decode(&[1]).unwrap_err();
+        7u8.encode(2).unwrap_err();
+
+        assert_eq!(String::from("ab").encode(4).unwrap(), b"ab\0\0");
+        String::from("abcd").encode(4).unwrap_err();
+        assert_eq!(String::decode(b"nxt\0junk").unwrap(), "nxt");
+
+        assert_eq!(RunState::decode(&[0x20]).unwrap(), RunState::Running);
+        RunState::decode(&[0x21]).unwrap_err();
+    }
+
+    #[test]
+    fn layouts() {
+        assert_eq!(display::NORMAL.offset, 119);
+        assert_eq!(display::POPUP.offset, 919);
+        assert_eq!(output::tacho_limit(output::Port::C).offset, 76);
+        output::Port::try_from(OutPort::AB).unwrap_err();
+        output::Port::try_from(OutPort::All).unwrap_err();
+        let conn = comm::ConnectionIndex::new(1).unwrap();
+        assert_eq!(comm::connection_name(conn).offset, 985);
+        assert!(comm::ConnectionIndex::new(4).is_none());
+        assert!(comm::DeviceIndex::new(30).is_none());
+        assert_eq!(comm::USB_STATE.offset, 1886);
+        assert_eq!(ui::ABORT_FLAG.offset, 40);
+        for (idx, module) in MODULES.iter().enumerate() {
+            assert!(!MODULES[..idx].contains(module));
+        }
+    }
+
+    #[tokio::test]
+    async fn read_write_fields() {
+        let mut brick = Brick::default();
+        brick.add_iomap(ui::MODULE, 64);
+        brick.add_iomap(display::MODULE, 1719);
+        let (nxt, state) = mock::connect(brick).await;
+
+        nxt.write_field(ui::VOLUME, &3).await.unwrap();
+        assert_eq!(state.lock().unwrap().iomaps[&ui::MODULE.id][36], 3);
+        assert_eq!(nxt.read_field(ui::VOLUME).await.unwrap(), 3);
+
+        // large fields are split across several requests
+        let screen = (0..=255).cycle().take(800).collect::<Vec<u8>>();
+        nxt.write_field(display::NORMAL, &screen).await.unwrap();
+        assert_eq!(nxt.read_field(display::NORMAL).await.unwrap(), screen);
+        assert_eq!(&nxt.get_display_data().await.unwrap()[..], screen);
+
+        // the command module has no iomap on the mock
+        nxt.read_field(command::PROG_STATUS).await.unwrap_err();
+    }
+}
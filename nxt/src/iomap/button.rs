@@ -0,0 +1,84 @@
+//! Iomap of the button module, which debounces the brick's buttons and
+//! counts press and release events
+
+use super::{entry_offset, Field, Module};
+
+/// The button module
+pub const MODULE: Module = Module {
+    name: "Button.mod",
+    id: 0x0004_0001,
+};
+
+/// Size of the per-button counter struct
+const COUNTER_SIZE: u16 = 8;
+/// Offset of the array of button states
+const STATE_OFFSET: u16 = 32;
+
+/// Set when the button is pressed
+pub const STATE_PRESSED_EV: u8 = 0x01;
+/// Set when the button is released after a short press
+pub const STATE_SHORT_RELEASED_EV: u8 = 0x02;
+/// Set when the button has been held down for a long press
+pub const STATE_LONG_PRESSED_EV: u8 = 0x04;
+/// Set when the button is released after a long press
+pub const STATE_LONG_RELEASED_EV: u8 = 0x08;
+/// Set while the button is held down
+pub const STATE_PRESSED: u8 = 0x80;
+
+/// The buttons on the front of the brick
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
+#[cfg_attr(feature = "strum", derive(strum_macros::EnumIter))]
+#[repr(u8)]
+pub enum Button {
+    /// The dark grey button below the others
+    Exit = 0,
+    /// The right arrow
+    Right = 1,
+    /// The left arrow
+    Left = 2,
+    /// The orange button in the middle
+    Enter = 3,
+}
+
+/// Describe a counter for the given button
+const fn counter(button: Button, offset: u16) -> Field<u8> {
+    let base = entry_offset(0, COUNTER_SIZE, button as u8);
+    Field::new(MODULE, base + offset, 1)
+}
+
+/// Number of times the button has been pressed
+#[must_use]
+pub const fn pressed_count(button: Button) -> Field<u8> {
+    counter(button, 0)
+}
+
+/// Number of long presses of the button
+#[must_use]
+pub const fn long_press_count(button: Button) -> Field<u8> {
+    counter(button, 1)
+}
+
+/// Number of releases after a short press
+#[must_use]
+pub const fn short_release_count(button: Button) -> Field<u8> {
+    counter(button, 2)
+}
+
+/// Number of releases after a long press
+#[must_use]
+pub const fn long_release_count(button: Button) -> Field<u8> {
+    counter(button, 3)
+}
+
+/// Total number of releases of the button
+#[must_use]
+pub const fn release_count(button: Button) -> Field<u8> {
+    counter(button, 4)
+}
+
+/// Button state flags, a combination of the `STATE_*` constants
+#[must_use]
+pub const fn state(button: Button) -> Field<u8> {
+    let offset = entry_offset(STATE_OFFSET, 1, button as u8);
+    Field::new(MODULE, offset, 1)
+}
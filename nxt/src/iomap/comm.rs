@@ -0,0 +1,193 @@
+//! Iomap of the comm module, which handles USB, Bluetooth and RS-485
+//! communication
+
+use super::{entry_offset, Field, Module};
+
+/// The comm module
+pub const MODULE: Module = Module {
+    name: "Comm.mod",
+    id: 0x0005_0001,
+};
+
+/// Number of entries in the Bluetooth device table
+pub const MAX_DEVICES: u8 = 30;
+/// Number of Bluetooth connections; 0 is the master, 1-3 are slaves
+pub const MAX_CONNECTIONS: u8 = 4;
+/// Size of a Bluetooth address field
+const ADDR_LEN: u16 = 7;
+/// Offset of the Bluetooth device table
+const DEVICE_TABLE_OFFSET: u16 = 8;
+/// Size of a device table entry
+const DEVICE_SIZE: u16 = 31;
+/// Offset of the Bluetooth connection table
+const CONNECT_TABLE_OFFSET: u16 = 938;
+/// Size of a connection table entry
+const CONNECTION_SIZE: u16 = 47;
+
+/// Index of an entry in the Bluetooth device table, from 0 to
+/// [`MAX_DEVICES`] - 1
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
+pub struct DeviceIndex(u8);
+
+impl DeviceIndex {
+    /// The entry with the given index, or `None` if it is out of range
+    #[must_use]
+    pub const fn new(idx: u8) -> Option<Self> {
+        if idx < MAX_DEVICES {
+            Some(Self(idx))
+        } else {
+            None
+        }
+    }
+
+    /// The index as a number
+    #[must_use]
+    pub const fn get(self) -> u8 {
+        self.0
+    }
+}
+
+/// Index of a Bluetooth connection, from 0 to [`MAX_CONNECTIONS`] - 1
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
+pub struct ConnectionIndex(u8);
+
+impl ConnectionIndex {
+    /// The connection with the given number, or `None` if it is out of
+    /// range
+    #[must_use]
+    pub const fn new(idx: u8) -> Option<Self> {
+        if idx < MAX_CONNECTIONS {
+            Some(Self(idx))
+        } else {
+            None
+        }
+    }
+
+    /// The connection number
+    #[must_use]
+    pub const fn get(self) -> u8 {
+        self.0
+    }
+}
+
+/// Describe a field of the given device table entry
+const fn device_field<T>(idx: DeviceIndex, offset: u16, len: u16) -> Field<T> {
+    let base = entry_offset(DEVICE_TABLE_OFFSET, DEVICE_SIZE, idx.0);
+    Field::new(MODULE, base + offset, len)
+}
+
+/// Describe a field of the given connection table entry
+const fn connection_field<T>(
+    idx: ConnectionIndex,
+    offset: u16,
+    len: u16,
+) -> Field<T> {
+    let base = entry_offset(CONNECT_TABLE_OFFSET, CONNECTION_SIZE, idx.0);
+    Field::new(MODULE, base + offset, len)
+}
+
+/// Name of a known Bluetooth device
+#[must_use]
+pub const fn device_name(idx: DeviceIndex) -> Field<String> {
+    device_field(idx, 0, 16)
+}
+
+/// Class of a known Bluetooth device
+#[must_use]
+pub const fn device_class(idx: DeviceIndex) -> Field<u32> {
+    device_field(idx, 16, 4)
+}
+
+/// Address of a known Bluetooth device
+#[must_use]
+pub const fn device_addr(idx: DeviceIndex) -> Field<Vec<u8>> {
+    device_field(idx, 20, ADDR_LEN)
+}
+
+/// Status of a known Bluetooth device
+#[must_use]
+pub const fn device_status(idx: DeviceIndex) -> Field<u8> {
+    device_field(idx, 27, 1)
+}
+
+/// Name of the device on a Bluetooth connection, empty if unused
+#[must_use]
+pub const fn connection_name(idx: ConnectionIndex) -> Field<String> {
+    connection_field(idx, 0, 16)
+}
+
+/// Class of the device on a Bluetooth connection
+#[must_use]
+pub const fn connection_class(idx: ConnectionIndex) -> Field<u32> {
+    connection_field(idx, 16, 4)
+}
+
+/// PIN code used for a Bluetooth connection
+#[must_use]
+pub const fn connection_pin(idx: ConnectionIndex) -> Field<String> {
+    connection_field(idx, 20, 16)
+}
+
+/// Address of the device on a Bluetooth connection
+#[must_use]
+pub const fn connection_addr(idx: ConnectionIndex) -> Field<Vec<u8>> {
+    connection_field(idx, 36, ADDR_LEN)
+}
+
+/// Bluecore handle number of a Bluetooth connection
+#[must_use]
+pub const fn connection_handle(idx: ConnectionIndex) -> Field<u8> {
+    connection_field(idx, 43, 1)
+}
+
+/// Stream status of a Bluetooth connection
+#[must_use]
+pub const fn connection_stream_status(idx: ConnectionIndex) -> Field<u8> {
+    connection_field(idx, 44, 1)
+}
+
+/// Link quality of a Bluetooth connection
+#[must_use]
+pub const fn connection_link_quality(idx: ConnectionIndex) -> Field<u8> {
+    connection_field(idx, 45, 1)
+}
+
+/// Name of this brick
+pub const BRICK_NAME: Field<String> = Field::new(MODULE, 1126, 16);
+/// Firmware version of the Bluetooth chip
+pub const BLUECORE_VERSION: Field<u16> = Field::new(MODULE, 1142, 2);
+/// Bluetooth address of this brick
+pub const BT_ADDR: Field<Vec<u8>> = Field::new(MODULE, 1144, ADDR_LEN);
+/// Bluetooth state flags
+pub const BT_STATE_STATUS: Field<u8> = Field::new(MODULE, 1151, 1);
+/// Bluetooth hardware state
+pub const BT_HW_STATUS: Field<u8> = Field::new(MODULE, 1152, 1);
+/// Bluetooth inquiry timeout
+pub const BT_TIMEOUT: Field<u8> = Field::new(MODULE, 1153, 1);
+/// Bluetooth input buffer
+pub const BT_IN_BUF: Field<Vec<u8>> = Field::new(MODULE, 1157, 128);
+/// Bluetooth output buffer
+pub const BT_OUT_BUF: Field<Vec<u8>> = Field::new(MODULE, 1287, 128);
+/// RS-485 input buffer
+pub const HS_IN_BUF: Field<Vec<u8>> = Field::new(MODULE, 1417, 128);
+/// RS-485 output buffer
+pub const HS_OUT_BUF: Field<Vec<u8>> = Field::new(MODULE, 1547, 128);
+/// USB input buffer. Each USB buffer is followed by its in and out
+/// pointers and two spare bytes.
+pub const USB_IN_BUF: Field<Vec<u8>> = Field::new(MODULE, 1677, 64);
+/// USB output buffer
+pub const USB_OUT_BUF: Field<Vec<u8>> = Field::new(MODULE, 1745, 64);
+/// USB poll buffer, filled by programs for the host to read
+pub const USB_POLL_BUF: Field<Vec<u8>> = Field::new(MODULE, 1813, 64);
+/// Number of entries in the Bluetooth device table
+pub const BT_DEVICE_COUNT: Field<u8> = Field::new(MODULE, 1881, 1);
+/// Number of named entries in the Bluetooth device table
+pub const BT_DEVICE_NAME_COUNT: Field<u8> = Field::new(MODULE, 1882, 1);
+/// RS-485 flags
+pub const HS_FLAGS: Field<u8> = Field::new(MODULE, 1883, 1);
+/// RS-485 speed
+pub const HS_SPEED: Field<u8> = Field::new(MODULE, 1884, 1);
+/// RS-485 state
+pub const HS_STATE: Field<u8> = Field::new(MODULE, 1885, 1);
+/// USB connection state
+pub const USB_STATE: Field<u8> = Field::new(MODULE, 1886, 1);
@@ -0,0 +1,58 @@
+//! Iomap of the command module, which runs the bytecode interpreter
+
+use super::{enum_field, Field, Module};
+use crate::{Error, Result};
+use num_derive::FromPrimitive;
+use num_traits::FromPrimitive;
+
+/// The command module
+pub const MODULE: Module = Module {
+    name: "Command.mod",
+    id: 0x0001_0001,
+};
+
+/// Executable format supported by the firmware, e.g. `MindstormsNXT`
+pub const FORMAT_STRING: Field<String> = Field::new(MODULE, 0, 16);
+/// Current system tick, in milliseconds
+pub const TICK: Field<u32> = Field::new(MODULE, 20, 4);
+/// Offset of the dataspace of the running program
+pub const OFFSET_DS: Field<u16> = Field::new(MODULE, 24, 2);
+/// Offset of the dope vector array of the running program
+pub const OFFSET_DVA: Field<u16> = Field::new(MODULE, 26, 2);
+/// State of the bytecode interpreter
+pub const PROG_STATUS: Field<ProgStatus> = Field::new(MODULE, 28, 1);
+/// Whether the interpreter is running
+pub const AWAKE: Field<bool> = Field::new(MODULE, 29, 1);
+/// Set to start the program named in [`FILE_NAME`]
+pub const ACTIVATE_FLAG: Field<u8> = Field::new(MODULE, 30, 1);
+/// Set to stop the running program
+pub const DEACTIVATE_FLAG: Field<u8> = Field::new(MODULE, 31, 1);
+/// Name of the running (or last run) program
+pub const FILE_NAME: Field<String> = Field::new(MODULE, 32, 20);
+
+/// State of the bytecode interpreter
+#[derive(Copy, Clone, Debug, PartialEq, Eq, FromPrimitive)]
+#[repr(u8)]
+pub enum ProgStatus {
+    /// No program has been run
+    Idle = 0,
+    /// The last program finished normally
+    Ok = 1,
+    /// A program is running
+    Running = 2,
+    /// The last program stopped with an error
+    Error = 3,
+    /// The last program was aborted
+    Abort = 4,
+    /// The interpreter is being reset
+    Reset = 5,
+}
+
+impl TryFrom<u8> for ProgStatus {
+    type Error = Error;
+    fn try_from(code: u8) -> Result<Self> {
+        Self::from_u8(code).ok_or(Error::Parse("Invalid ProgStatus"))
+    }
+}
+
+enum_field!(ProgStatus);
@@ -0,0 +1,46 @@
+//! Iomap of the display module, which owns the LCD and its frame
+//! buffers
+
+use super::{Field, Module};
+use crate::DISPLAY_DATA_LEN;
+
+/// The display module
+pub const MODULE: Module = Module {
+    name: "Display.mod",
+    id: 0x000a_0001,
+};
+
+/// Length of a frame buffer, in bytes
+#[allow(clippy::cast_possible_truncation)]
+const SCREEN_LEN: u16 = DISPLAY_DATA_LEN as u16; // 800 fits in u16
+
+/// Set to erase the areas of the screen given in the mask
+pub const ERASE_MASK: Field<u32> = Field::new(MODULE, 4, 4);
+/// Set to redraw the areas of the screen given in the mask
+pub const UPDATE_MASK: Field<u32> = Field::new(MODULE, 8, 4);
+/// Status icons shown in the top row
+pub const STATUS_ICONS: Field<Vec<u8>> = Field::new(MODULE, 108, 4);
+/// Step icons shown by the on-brick programming screens
+pub const STEP_ICONS: Field<Vec<u8>> = Field::new(MODULE, 112, 5);
+/// Display flags, a combination of the `FLAG_*` constants
+pub const FLAGS: Field<u8> = Field::new(MODULE, 117, 1);
+/// Bitmask of text lines to draw centred
+pub const TEXT_LINES_CENTER_FLAGS: Field<u8> = Field::new(MODULE, 118, 1);
+/// The normal frame buffer, in the column-major format described in
+/// [`display_data_to_raster`](crate::system::display_data_to_raster)
+pub const NORMAL: Field<Vec<u8>> = Field::new(MODULE, 119, SCREEN_LEN);
+/// The popup frame buffer, shown instead of the normal one when
+/// [`FLAG_POPUP`] is set
+pub const POPUP: Field<Vec<u8>> =
+    Field::new(MODULE, 119 + SCREEN_LEN, SCREEN_LEN);
+
+/// The display is switched on
+pub const FLAG_ON: u8 = 0x01;
+/// The display is refreshed from the frame buffer
+pub const FLAG_REFRESH: u8 = 0x02;
+/// Show the popup frame buffer rather than the normal one
+pub const FLAG_POPUP: u8 = 0x08;
+/// Refreshing is disabled
+pub const FLAG_REFRESH_DISABLED: u8 = 0x40;
+/// The display is busy updating
+pub const FLAG_BUSY: u8 = 0x80;
@@ -0,0 +1,99 @@
+//! Iomap of the input module, which reads the sensors. Fields are
+//! repeated for each port.
+
+use super::{entry_offset, Field, Module};
+use crate::sensor::{InPort, SensorMode, SensorType};
+
+/// The input module
+pub const MODULE: Module = Module {
+    name: "Input.mod",
+    id: 0x0003_0001,
+};
+
+/// Size of the per-port struct
+const PORT_SIZE: u16 = 20;
+
+/// Describe a field within the struct for the given port
+const fn port_field<T>(port: InPort, offset: u16, len: u16) -> Field<T> {
+    let base = entry_offset(0, PORT_SIZE, port as u8);
+    Field::new(MODULE, base + offset, len)
+}
+
+/// Zero offset for custom sensors
+#[must_use]
+pub const fn custom_zero_offset(port: InPort) -> Field<u16> {
+    port_field(port, 0, 2)
+}
+
+/// Raw value from the analogue to digital converter
+#[must_use]
+pub const fn ad_raw(port: InPort) -> Field<u16> {
+    port_field(port, 2, 2)
+}
+
+/// Raw value after sensor-type specific processing
+#[must_use]
+pub const fn sensor_raw(port: InPort) -> Field<u16> {
+    port_field(port, 4, 2)
+}
+
+/// Scaled value according to the sensor mode
+#[must_use]
+pub const fn sensor_value(port: InPort) -> Field<i16> {
+    port_field(port, 6, 2)
+}
+
+/// Configured sensor type
+#[must_use]
+pub const fn sensor_type(port: InPort) -> Field<SensorType> {
+    port_field(port, 8, 1)
+}
+
+/// Configured sensor mode
+#[must_use]
+pub const fn sensor_mode(port: InPort) -> Field<SensorMode> {
+    port_field(port, 9, 1)
+}
+
+/// Boolean interpretation of the sensor value
+#[must_use]
+pub const fn sensor_boolean(port: InPort) -> Field<bool> {
+    port_field(port, 10, 1)
+}
+
+/// Direction of the digital pins
+#[must_use]
+pub const fn digi_pins_dir(port: InPort) -> Field<u8> {
+    port_field(port, 11, 1)
+}
+
+/// State of the digital pins when used as inputs
+#[must_use]
+pub const fn digi_pins_in(port: InPort) -> Field<u8> {
+    port_field(port, 12, 1)
+}
+
+/// State of the digital pins when used as outputs
+#[must_use]
+pub const fn digi_pins_out(port: InPort) -> Field<u8> {
+    port_field(port, 13, 1)
+}
+
+/// Full scale value for custom sensors, in percent
+#[must_use]
+pub const fn custom_pct_full_scale(port: InPort) -> Field<u8> {
+    port_field(port, 14, 1)
+}
+
+/// Whether the custom sensor is active
+#[must_use]
+pub const fn custom_active_status(port: InPort) -> Field<u8> {
+    port_field(port, 15, 1)
+}
+
+/// Set while the sensor value is not yet valid after a change of type
+/// or mode
+#[must_use]
+pub const fn invalid_data(port: InPort) -> Field<bool> {
+    port_field(port, 16, 1)
+}
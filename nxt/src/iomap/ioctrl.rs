@@ -0,0 +1,18 @@
+//! Iomap of the IO control module, which powers the brick down or
+//! reboots it into firmware update mode
+
+use super::{Field, Module};
+
+/// The IO control module
+pub const MODULE: Module = Module {
+    name: "IOCtrl.mod",
+    id: 0x0006_0001,
+};
+
+/// Write one of the `POWER_*` constants to act on it
+pub const POWER_ON: Field<u16> = Field::new(MODULE, 0, 2);
+
+/// Switch the brick off
+pub const POWER_DOWN: u16 = 0x5a00;
+/// Reboot into SAM-BA firmware update mode
+pub const POWER_BOOT: u16 = 0xa55a;
@@ -0,0 +1,12 @@
+//! Iomap of the loader module, which manages the file system
+
+use super::{Field, Module};
+
+/// The loader module
+pub const MODULE: Module = Module {
+    name: "Loader.mod",
+    id: 0x0009_0001,
+};
+
+/// Free user flash, in bytes
+pub const FREE_USER_FLASH: Field<u32> = Field::new(MODULE, 4, 4);
@@ -0,0 +1,105 @@
+//! Iomap of the low speed module, which drives I2C sensors. Fields are
+//! repeated for each input port.
+
+use super::{entry_offset, Field, Module};
+use crate::sensor::InPort;
+
+/// The low speed module
+pub const MODULE: Module = Module {
+    name: "Low Speed.mod",
+    id: 0x000b_0001,
+};
+
+/// Size of a buffer's data
+pub const BUF_LEN: u16 = 16;
+/// Size of a buffer struct: the data plus in and out pointers and the
+/// number of bytes to receive
+const BUF_SIZE: u16 = BUF_LEN + 3;
+/// Offset of the array of input buffers
+const IN_BUF_OFFSET: u16 = 0;
+/// Offset of the array of output buffers
+const OUT_BUF_OFFSET: u16 = 76;
+
+/// Describe a field of the input or output buffer for the given port
+const fn buf_field<T>(
+    base: u16,
+    port: InPort,
+    offset: u16,
+    len: u16,
+) -> Field<T> {
+    let base = entry_offset(base, BUF_SIZE, port as u8);
+    Field::new(MODULE, base + offset, len)
+}
+
+/// Describe a per-port byte in an array starting at `base`
+const fn port_byte(base: u16, port: InPort) -> Field<u8> {
+    Field::new(MODULE, entry_offset(base, 1, port as u8), 1)
+}
+
+/// Data received from the sensor
+#[must_use]
+pub const fn in_buf(port: InPort) -> Field<Vec<u8>> {
+    buf_field(IN_BUF_OFFSET, port, 0, BUF_LEN)
+}
+
+/// Write position in the input buffer
+#[must_use]
+pub const fn in_buf_in_ptr(port: InPort) -> Field<u8> {
+    buf_field(IN_BUF_OFFSET, port, BUF_LEN, 1)
+}
+
+/// Read position in the input buffer
+#[must_use]
+pub const fn in_buf_out_ptr(port: InPort) -> Field<u8> {
+    buf_field(IN_BUF_OFFSET, port, BUF_LEN + 1, 1)
+}
+
+/// Data to send to the sensor
+#[must_use]
+pub const fn out_buf(port: InPort) -> Field<Vec<u8>> {
+    buf_field(OUT_BUF_OFFSET, port, 0, BUF_LEN)
+}
+
+/// Write position in the output buffer
+#[must_use]
+pub const fn out_buf_in_ptr(port: InPort) -> Field<u8> {
+    buf_field(OUT_BUF_OFFSET, port, BUF_LEN, 1)
+}
+
+/// Read position in the output buffer
+#[must_use]
+pub const fn out_buf_out_ptr(port: InPort) -> Field<u8> {
+    buf_field(OUT_BUF_OFFSET, port, BUF_LEN + 1, 1)
+}
+
+/// Number of bytes expected in reply to the output buffer
+#[must_use]
+pub const fn bytes_to_rx(port: InPort) -> Field<u8> {
+    buf_field(OUT_BUF_OFFSET, port, BUF_LEN + 2, 1)
+}
+
+/// Communication mode of the port
+#[must_use]
+pub const fn mode(port: InPort) -> Field<u8> {
+    port_byte(152, port)
+}
+
+/// State of the communication channel
+#[must_use]
+pub const fn channel_state(port: InPort) -> Field<u8> {
+    port_byte(156, port)
+}
+
+/// Error from the last transaction
+#[must_use]
+pub const fn error_type(port: InPort) -> Field<u8> {
+    port_byte(160, port)
+}
+
+/// Overall state of the module
+pub const STATE: Field<u8> = Field::new(MODULE, 164, 1);
+/// Bus speed (unused by the LEGO firmware)
+pub const SPEED: Field<u8> = Field::new(MODULE, 165, 1);
+/// Bitmask of ports which send a stop rather than a restart condition
+/// between write and read
+pub const NO_RESTART_ON_READ: Field<u8> = Field::new(MODULE, 166, 1);
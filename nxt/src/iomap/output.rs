@@ -0,0 +1,153 @@
+//! Iomap of the output module, which drives the motors. Fields are
+//! repeated for each of the individual ports A, B and C.
+
+use super::{entry_offset, Field, Module};
+use crate::{
+    motor::{OutPort, RegulationMode, RunState},
+    Error, Result,
+};
+
+/// The output module
+pub const MODULE: Module = Module {
+    name: "Output.mod",
+    id: 0x0002_0001,
+};
+
+/// Size of the per-port struct
+const PORT_SIZE: u16 = 32;
+
+/// A single output port. Unlike [`OutPort`], this can't name a group of
+/// ports, which have no fields of their own.
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
+#[cfg_attr(feature = "strum", derive(strum_macros::EnumIter))]
+#[repr(u8)]
+#[allow(missing_docs)]
+pub enum Port {
+    A = 0,
+    B = 1,
+    C = 2,
+}
+
+impl TryFrom<OutPort> for Port {
+    type Error = Error;
+    fn try_from(port: OutPort) -> Result<Self> {
+        match port {
+            OutPort::A => Ok(Self::A),
+            OutPort::B => Ok(Self::B),
+            OutPort::C => Ok(Self::C),
+            _ => Err(Error::Parse("Not a single output port")),
+        }
+    }
+}
+
+impl From<Port> for OutPort {
+    fn from(port: Port) -> Self {
+        match port {
+            Port::A => Self::A,
+            Port::B => Self::B,
+            Port::C => Self::C,
+        }
+    }
+}
+
+/// Describe a field within the struct for the given port
+const fn port_field<T>(port: Port, offset: u16, len: u16) -> Field<T> {
+    let base = entry_offset(0, PORT_SIZE, port as u8);
+    Field::new(MODULE, base + offset, len)
+}
+
+/// Tachometer count since the last reset
+#[must_use]
+pub const fn tacho_count(port: Port) -> Field<i32> {
+    port_field(port, 0, 4)
+}
+
+/// Tachometer count relative to the last motor command
+#[must_use]
+pub const fn block_tacho_count(port: Port) -> Field<i32> {
+    port_field(port, 4, 4)
+}
+
+/// Tachometer count relative to the last rotation reset
+#[must_use]
+pub const fn rotation_count(port: Port) -> Field<i32> {
+    port_field(port, 8, 4)
+}
+
+/// Number of degrees to turn before stopping
+#[must_use]
+pub const fn tacho_limit(port: Port) -> Field<u32> {
+    port_field(port, 12, 4)
+}
+
+/// Motor speed, in RPM (unused by the LEGO firmware)
+#[must_use]
+pub const fn motor_rpm(port: Port) -> Field<i16> {
+    port_field(port, 16, 2)
+}
+
+/// Flags indicating which settings should be applied
+#[must_use]
+pub const fn flags(port: Port) -> Field<u8> {
+    port_field(port, 18, 1)
+}
+
+/// Output mode bits, see [`OutMode`](crate::motor::OutMode)
+#[must_use]
+pub const fn mode(port: Port) -> Field<u8> {
+    port_field(port, 19, 1)
+}
+
+/// Commanded power, -100 to 100
+#[must_use]
+pub const fn speed(port: Port) -> Field<i8> {
+    port_field(port, 20, 1)
+}
+
+/// Power currently applied by the regulator
+#[must_use]
+pub const fn actual_speed(port: Port) -> Field<i8> {
+    port_field(port, 21, 1)
+}
+
+/// Proportional term of the regulator
+#[must_use]
+pub const fn reg_p(port: Port) -> Field<u8> {
+    port_field(port, 22, 1)
+}
+
+/// Integral term of the regulator
+#[must_use]
+pub const fn reg_i(port: Port) -> Field<u8> {
+    port_field(port, 23, 1)
+}
+
+/// Derivative term of the regulator
+#[must_use]
+pub const fn reg_d(port: Port) -> Field<u8> {
+    port_field(port, 24, 1)
+}
+
+/// Current run state
+#[must_use]
+pub const fn run_state(port: Port) -> Field<RunState> {
+    port_field(port, 25, 1)
+}
+
+/// Current regulation mode
+#[must_use]
+pub const fn reg_mode(port: Port) -> Field<RegulationMode> {
+    port_field(port, 26, 1)
+}
+
+/// Whether the motor is unable to reach the commanded speed
+#[must_use]
+pub const fn overloaded(port: Port) -> Field<bool> {
+    port_field(port, 27, 1)
+}
+
+/// Turn ratio when synchronised with another motor
+#[must_use]
+pub const fn sync_turn(port: Port) -> Field<i8> {
+    port_field(port, 28, 1)
+}
@@ -0,0 +1,49 @@
+//! Iomap of the sound module, which plays tones and sound files
+
+use super::{Field, Module};
+
+/// The sound module
+pub const MODULE: Module = Module {
+    name: "Sound.mod",
+    id: 0x0008_0001,
+};
+
+/// Frequency of the tone to play, in Hz
+pub const FREQ: Field<u16> = Field::new(MODULE, 0, 2);
+/// Duration of the tone to play, in milliseconds
+pub const DURATION: Field<u16> = Field::new(MODULE, 2, 2);
+/// Sample rate of the sound file being played, in Hz
+pub const SAMPLE_RATE: Field<u16> = Field::new(MODULE, 4, 2);
+/// Name of the sound file to play
+pub const FILENAME: Field<String> = Field::new(MODULE, 6, 20);
+/// Request flags, one of the `FLAGS_*` constants
+pub const FLAGS: Field<u8> = Field::new(MODULE, 26, 1);
+/// Current state, one of the `STATE_*` constants
+pub const STATE: Field<u8> = Field::new(MODULE, 27, 1);
+/// Play mode, one of the `MODE_*` constants
+pub const MODE: Field<u8> = Field::new(MODULE, 28, 1);
+/// Volume, 0 to 4
+pub const VOLUME: Field<u8> = Field::new(MODULE, 29, 1);
+
+/// Nothing to do
+pub const FLAGS_IDLE: u8 = 0x00;
+/// Start playing according to the other fields
+pub const FLAGS_UPDATE: u8 = 0x01;
+/// Currently playing
+pub const FLAGS_RUNNING: u8 = 0x02;
+
+/// Not playing
+pub const STATE_IDLE: u8 = 0x00;
+/// Playing a sound file
+pub const STATE_FILE: u8 = 0x02;
+/// Playing a tone
+pub const STATE_TONE: u8 = 0x03;
+/// Stop playing
+pub const STATE_STOP: u8 = 0x04;
+
+/// Play the sound file once
+pub const MODE_ONCE: u8 = 0x00;
+/// Play the sound file repeatedly
+pub const MODE_LOOP: u8 = 0x01;
+/// Play a tone
+pub const MODE_TONE: u8 = 0x02;
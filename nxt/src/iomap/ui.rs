@@ -0,0 +1,61 @@
+//! Iomap of the UI module, which runs the on-brick menus
+
+use super::{Field, Module};
+
+/// The UI module
+pub const MODULE: Module = Module {
+    name: "Ui.mod",
+    id: 0x000c_0001,
+};
+
+/// Battery voltage, in mV
+pub const BATTERY_VOLTAGE: Field<u16> = Field::new(MODULE, 4, 2);
+/// Name of the program to run with [`FLAGS_EXECUTE_LMS_FILE`]
+pub const LMS_FILENAME: Field<String> = Field::new(MODULE, 6, 20);
+/// UI flags, a combination of the `FLAGS_*` constants
+pub const FLAGS: Field<u8> = Field::new(MODULE, 26, 1);
+/// State of the UI state machine
+pub const STATE: Field<u8> = Field::new(MODULE, 27, 1);
+/// Button press to be handled by the UI
+pub const BUTTON: Field<u8> = Field::new(MODULE, 28, 1);
+/// State of the running program indicator
+pub const RUN_STATE: Field<u8> = Field::new(MODULE, 29, 1);
+/// Battery level shown in the status bar, 0 to 4
+pub const BATTERY_STATE: Field<u8> = Field::new(MODULE, 30, 1);
+/// Bluetooth state shown in the status bar
+pub const BLUETOOTH_STATE: Field<u8> = Field::new(MODULE, 31, 1);
+/// USB state shown in the status bar
+pub const USB_STATE: Field<u8> = Field::new(MODULE, 32, 1);
+/// Sleep timeout, in minutes; 0 disables sleeping
+pub const SLEEP_TIMEOUT: Field<u8> = Field::new(MODULE, 33, 1);
+/// Minutes until the brick goes to sleep
+pub const SLEEP_TIMER: Field<u8> = Field::new(MODULE, 34, 1);
+/// Whether a rechargeable battery is fitted
+pub const RECHARGEABLE: Field<bool> = Field::new(MODULE, 35, 1);
+/// Volume setting, 0 to 4
+pub const VOLUME: Field<u8> = Field::new(MODULE, 36, 1);
+/// Error shown by the UI
+pub const ERROR: Field<u8> = Field::new(MODULE, 37, 1);
+/// On-brick program step pointer
+pub const OBP_POINTER: Field<u8> = Field::new(MODULE, 38, 1);
+/// Set to switch the brick off
+pub const FORCE_OFF: Field<bool> = Field::new(MODULE, 39, 1);
+/// Set to abort the running program, as if the exit button was pressed
+pub const ABORT_FLAG: Field<u8> = Field::new(MODULE, 40, 1);
+
+/// Update the UI from the other fields
+pub const FLAGS_UPDATE: u8 = 0x01;
+/// Ignore the left, right and enter buttons
+pub const FLAGS_DISABLE_LEFT_RIGHT_ENTER: u8 = 0x02;
+/// Ignore the exit button
+pub const FLAGS_DISABLE_EXIT: u8 = 0x04;
+/// Redraw the status bar
+pub const FLAGS_REDRAW_STATUS: u8 = 0x08;
+/// Reset the sleep timer
+pub const FLAGS_RESET_SLEEP_TIMER: u8 = 0x10;
+/// Run the program named in [`LMS_FILENAME`]
+pub const FLAGS_EXECUTE_LMS_FILE: u8 = 0x20;
+/// The UI is busy running a program
+pub const FLAGS_BUSY: u8 = 0x40;
+/// Keep the status bar up to date
+pub const FLAGS_ENABLE_STATUS_UPDATE: u8 = 0x80;
@@ -16,9 +16,9 @@
 pub use error::{Error, Result};
 
 use std::{
+    collections::HashMap,
     fmt::{self, Debug, Formatter},
-    io::{Cursor, Write},
-    sync::Arc,
+    sync::{Arc, Mutex},
 };
 
 #[macro_use]
@@ -27,12 +27,34 @@ extern crate tracing;
 #[cfg(feature = "strum")]
 pub use strum::IntoEnumIterator;
 
+#[cfg(feature = "backup")]
+pub mod backup;
+pub mod button;
+pub mod console;
+pub mod datalog;
+pub mod draw;
 mod error;
+pub mod firmware;
+pub mod flash;
+pub mod font;
+pub mod image;
+pub mod iomap;
+pub mod mailbox;
+pub mod mirror;
 pub mod motor;
+mod poll;
 mod protocol;
+pub mod relay;
+pub mod ric;
+pub mod rpc;
+pub mod rxe;
+pub mod samba;
 pub mod sensor;
+pub mod settings;
 mod socket;
+pub mod sound;
 pub mod system;
+pub mod vm;
 
 #[cfg(feature = "usb")]
 pub use socket::usb::Usb;
@@ -41,39 +63,54 @@ pub use socket::usb::Usb;
 pub use socket::bluetooth::Bluetooth;
 
 use motor::{OutMode, OutPort, OutputState, RegulationMode, RunState};
+pub use protocol::DeviceError;
 use protocol::{Opcode, Packet};
 use sensor::{InPort, InputValues, SensorMode, SensorType};
 use socket::Socket;
 use system::{
-    BufType, DeviceInfo, FileHandle, FindFileHandle, FwVersion, ModuleHandle,
+    BufType, DeviceInfo, DisplayRaster, FileHandle, FileInfo, FileType,
+    FindFileHandle, FwVersion, ModuleHandle, ModuleInfo, WriteMode, ALL_FILES,
 };
 
 /// Maximum length of a USB message
 pub const MAX_MESSAGE_LEN: usize = 58;
 /// Length of the brick name field
 const MAX_NAME_LEN: usize = 15;
+/// Largest amount of data that can be read from a file in one request
+/// (packet size less the reply header, status, handle and length)
+pub const FILE_READ_CHUNK_SIZE: usize = 64 - 6;
+/// Largest amount of data that can be written to a file in one request
+/// (packet size less the request header and handle)
+pub const FILE_WRITE_CHUNK_SIZE: usize = 64 - 3;
 /// Largest inbox ID for inter-brick messaging
 pub const MAX_INBOX_ID: u8 = 19;
+/// Shortest interval at which the streaming methods poll the brick
+///
+/// Shorter intervals passed to those methods, such as
+/// [`Nxt::mirror_display`], are raised to it, including zero. The
+/// streams tick a Tokio timer, so they need a runtime with the timer
+/// enabled.
+pub const MIN_POLL_INTERVAL: std::time::Duration =
+    std::time::Duration::from_millis(1);
 
-/// Module ID of the display (tested on the NBC enhanced firmware, may
-/// differ for the official LEGO firmware)
-const MOD_DISPLAY: u32 = 0xa0001;
-/// Offset of the display data into the display iomap struct; consult
-/// the firmware source for details
-const DISPLAY_DATA_OFFSET: u16 = 119;
 /// Width of NXT LCD screen in pixels
 pub const DISPLAY_WIDTH: usize = 100;
 /// Height of NXT LCD screen in pixels
 pub const DISPLAY_HEIGHT: usize = 64;
 /// Total number of LCD pixels
 pub const DISPLAY_DATA_LEN: usize = DISPLAY_WIDTH * DISPLAY_HEIGHT / 8;
-/// Chunk size to use when requesting display data from NXT. Due to the
-/// packet size restriction the display must be refreshed in chunks.
-const DISPLAY_DATA_CHUNK_SIZE: u16 = 32;
-/// Number of chunks required to retrieve the complete display data
-#[allow(clippy::cast_possible_truncation)]
-const DISPLAY_NUM_CHUNKS: u16 =
-    DISPLAY_DATA_LEN as u16 / DISPLAY_DATA_CHUNK_SIZE;
+/// Find the offset of the first byte which differs between the expected
+/// and actual data, including a difference in length
+fn first_mismatch(expected: &[u8], actual: &[u8]) -> Option<usize> {
+    expected
+        .iter()
+        .zip(actual)
+        .position(|(a, b)| a != b)
+        .or_else(|| {
+            (expected.len() != actual.len())
+                .then(|| expected.len().min(actual.len()))
+        })
+}
 
 /// Main interface to this crate, an `NXT` represents a connection to a
 /// programmable brick.
@@ -83,6 +120,8 @@ pub struct Nxt {
     device: Arc<dyn Socket + Send + Sync>,
     /// Name of the brick
     name: String,
+    /// Module IDs by name, filled in by [`Nxt::list_modules`]
+    module_ids: Arc<Mutex<HashMap<String, u32>>>,
 }
 
 impl Debug for Nxt {
@@ -117,6 +156,7 @@ impl Nxt {
         let mut nxt = Self {
             device: Arc::new(device),
             name: String::new(),
+            module_ids: Arc::default(),
         };
         let info = nxt.get_device_info().await?;
         debug!("Connected device is named `{}`", info.name);
@@ -175,21 +215,45 @@ impl Nxt {
     /// The data is in a slightly odd format; see
     /// [`system::display_data_to_raster`] for details.
     pub async fn get_display_data(&self) -> Result<[u8; DISPLAY_DATA_LEN]> {
-        let out = [0; DISPLAY_DATA_LEN];
-        let mut cur = Cursor::new(out);
-        for chunk_idx in 0..DISPLAY_NUM_CHUNKS {
-            let data = self
-                .read_io_map(
-                    MOD_DISPLAY,
-                    DISPLAY_DATA_OFFSET + chunk_idx * DISPLAY_DATA_CHUNK_SIZE,
-                    DISPLAY_DATA_CHUNK_SIZE,
-                )
-                .await?;
-            assert_eq!(data.len(), DISPLAY_DATA_CHUNK_SIZE.into());
-            cur.write_all(&data)?;
-        }
+        let data = self.read_field(iomap::display::NORMAL).await?;
+        // the field is exactly one screen long
+        Ok(data.try_into().unwrap())
+    }
 
-        Ok(cur.into_inner())
+    /// Overwrite the contents of the LCD screen, in the same format as
+    /// returned by [`Nxt::get_display_data`]. The firmware keeps drawing
+    /// its own UI over the top; see [`Nxt::set_ui_redraw`].
+    pub async fn set_display_data(
+        &self,
+        data: &[u8; DISPLAY_DATA_LEN],
+    ) -> Result<()> {
+        self.write_field(iomap::display::NORMAL, &data.to_vec())
+            .await
+    }
+
+    /// Draw the provided raster on the LCD screen
+    pub async fn draw_raster(&self, raster: &DisplayRaster) -> Result<()> {
+        self.set_display_data(&system::raster_to_display_data(raster))
+            .await
+    }
+
+    /// Enable or disable the firmware's periodic redraw of the status bar,
+    /// so that images drawn with [`Nxt::draw_raster`] stay on screen.
+    /// Navigating the on-brick menus still redraws the screen. Enabling
+    /// the redraw again also refreshes the status bar straight away.
+    pub async fn set_ui_redraw(&self, enabled: bool) -> Result<()> {
+        use iomap::ui;
+
+        let flags = self.read_field(ui::FLAGS).await?;
+        let flags = if enabled {
+            flags
+                | ui::FLAGS_ENABLE_STATUS_UPDATE
+                | ui::FLAGS_REDRAW_STATUS
+                | ui::FLAGS_UPDATE
+        } else {
+            flags & !ui::FLAGS_ENABLE_STATUS_UPDATE
+        };
+        self.write_field(ui::FLAGS, &flags).await
     }
 
     /// Retrieve the current battery level, in mV
@@ -352,16 +416,14 @@ impl Nxt {
         self.send(&pkt, true).await
     }
 
-    /// Write a message to the specified inbox. Returns an error if the
-    /// inbox ID is greater than [`MAX_INBOX_ID`] or of the message is
-    /// longer than [`MAX_MESSAGE_LEN`] bytes
+    /// Write a message to the specified inbox. A null terminator is
+    /// added. Returns an error if the inbox ID is greater than
+    /// [`MAX_INBOX_ID`] or if the message is longer than
+    /// [`MAX_MESSAGE_LEN`] bytes. See [`Nxt::send_message`] for typed
+    /// messages.
     pub async fn message_write(&self, inbox: u8, message: &[u8]) -> Result<()> {
-        if inbox > MAX_INBOX_ID {
-            return Err(Error::Serialise("Invalid mailbox ID"));
-        }
-        if message.len() > MAX_MESSAGE_LEN {
-            return Err(Error::Serialise("Message too long (max 58 bytes)"));
-        }
+        mailbox::check_inbox(inbox)?;
+        mailbox::check_message_len(message.len())?;
 
         let mut pkt = Packet::new(Opcode::DirectMessageWrite);
         pkt.push_u8(inbox);
@@ -464,6 +526,8 @@ impl Nxt {
         local_inbox: u8,
         remove: bool,
     ) -> Result<Vec<u8>> {
+        mailbox::check_inbox(remote_inbox)?;
+        mailbox::check_inbox(local_inbox)?;
         let mut pkt = Packet::new(Opcode::DirectMessageRead);
         pkt.push_u8(remote_inbox);
         pkt.push_u8(local_inbox);
@@ -490,18 +554,24 @@ impl Nxt {
         Ok(FileHandle { handle, len })
     }
 
-    /// Write the provided data to the previously opened file
+    /// Write the provided data to the previously opened file, returning
+    /// the number of bytes written. At most [`FILE_WRITE_CHUNK_SIZE`]
+    /// bytes can be written at once.
     pub async fn file_write(
         &self,
         handle: &FileHandle,
         data: &[u8],
-    ) -> Result<u32> {
+    ) -> Result<u16> {
+        if data.len() > FILE_WRITE_CHUNK_SIZE {
+            return Err(Error::Serialise("Data too long"));
+        }
+
         let mut pkt = Packet::new(Opcode::SystemWrite);
         pkt.push_u8(handle.handle);
         pkt.push_slice(data);
         let mut recv = self.send_recv(&pkt).await?;
         let _handle = recv.read_u8()?;
-        recv.read_u32()
+        recv.read_u16()
     }
 
     /// Open the specified file in `write data` mode and return its handle
@@ -548,18 +618,23 @@ impl Nxt {
         Ok(FileHandle { handle, len })
     }
 
-    /// Read data from the previously opened file
+    /// Read data from the previously opened file. At most
+    /// [`FILE_READ_CHUNK_SIZE`] bytes can be read at once.
     pub async fn file_read(
         &self,
         handle: &FileHandle,
-        len: u32,
+        len: u16,
     ) -> Result<Vec<u8>> {
-        let mut pkt = Packet::new(Opcode::SystemOpenread);
+        if usize::from(len) > FILE_READ_CHUNK_SIZE {
+            return Err(Error::Serialise("Read length too long"));
+        }
+
+        let mut pkt = Packet::new(Opcode::SystemRead);
         pkt.push_u8(handle.handle);
-        pkt.push_u32(len);
+        pkt.push_u16(len);
         let mut recv = self.send_recv(&pkt).await?;
         let _handle = recv.read_u8()?;
-        let len = recv.read_u8()?;
+        let len = recv.read_u16()?;
         let data = recv.read_slice(len as usize)?;
         Ok(data.to_vec())
     }
@@ -629,6 +704,137 @@ impl Nxt {
         Ok(FileHandle { handle, len })
     }
 
+    /// List all files on the brick matching the given pattern, e.g.
+    /// `*.*` or `*.rxe`. Returns an empty list if nothing matches.
+    pub async fn list_files(&self, pattern: &str) -> Result<Vec<FileInfo>> {
+        let mut handle = match self.file_find_first(pattern).await {
+            Ok(handle) => handle,
+            Err(Error::Device(DeviceError::FileNotFound)) => {
+                return Ok(Vec::new())
+            }
+            Err(e) => return Err(e),
+        };
+
+        let mut files = Vec::new();
+        let res = loop {
+            files.push(FileInfo {
+                name: handle.name.clone(),
+                len: handle.len,
+            });
+            match self.file_find_next(&handle).await {
+                Ok(next) => handle = next,
+                Err(Error::Device(
+                    DeviceError::FileNotFound | DeviceError::NoMoreFiles,
+                )) => break Ok(files),
+                Err(e) => break Err(e),
+            }
+        };
+
+        // the firmware may already have released the handle at the end
+        // of the search, so ignore any error here
+        let _ = self
+            .file_close(&FileHandle {
+                handle: handle.handle,
+                len: 0,
+            })
+            .await;
+        res
+    }
+
+    /// Read the entire contents of the named file from the brick
+    pub async fn download_file(&self, name: &str) -> Result<Vec<u8>> {
+        let handle = self.file_open_read(name).await?;
+        let res = self.download_from(&handle).await;
+        self.file_close(&handle).await?;
+        res
+    }
+
+    /// Read chunks from an open file until its full length has been
+    /// received
+    async fn download_from(&self, handle: &FileHandle) -> Result<Vec<u8>> {
+        let len = handle.len as usize;
+        let mut data = Vec::with_capacity(len);
+        while data.len() < len {
+            let count = (len - data.len()).min(FILE_READ_CHUNK_SIZE);
+            let chunk = self.file_read(handle, count.try_into()?).await?;
+            if chunk.is_empty() {
+                return Err(Error::Parse("File ended unexpectedly"));
+            }
+            data.extend(chunk);
+        }
+        Ok(data)
+    }
+
+    /// Write a new file to the brick, opening it in the [`WriteMode`]
+    /// appropriate for its [`FileType`]. Returns a `FileExists` error if
+    /// a file of the same name is already present.
+    pub async fn upload_file(&self, name: &str, data: &[u8]) -> Result<()> {
+        let len = data.len().try_into()?;
+        let handle = match FileType::from_name(name).write_mode() {
+            WriteMode::Normal => self.file_open_write(name, len).await?,
+            WriteMode::Linear => self.file_open_write_linear(name, len).await?,
+            WriteMode::Data => self.file_open_write_data(name, len).await?,
+        };
+        let res = self.upload_to(&handle, data).await;
+        self.file_close(&handle).await?;
+        res
+    }
+
+    /// Upload a file as with [`Nxt::upload_file`], then read it back
+    /// and compare it with the provided data. If the comparison fails
+    /// the file is deleted and the upload retried, up to `retries`
+    /// times. If every attempt fails the file is deleted and a
+    /// [`Error::VerifyMismatch`] identifying the first differing chunk
+    /// is returned.
+    ///
+    /// The available flash is checked before starting, returning
+    /// [`Error::InsufficientFlash`] if the file cannot fit.
+    pub async fn upload_file_verified(
+        &self,
+        name: &str,
+        data: &[u8],
+        retries: u32,
+    ) -> Result<()> {
+        let required = data.len().try_into()?;
+        let available = self.get_device_info().await?.flash;
+        if required > available {
+            return Err(Error::InsufficientFlash {
+                required,
+                available,
+            });
+        }
+
+        let mut attempt = 0;
+        loop {
+            self.upload_file(name, data).await?;
+            let readback = self.download_file(name).await?;
+            let Some(offset) = first_mismatch(data, &readback) else {
+                return Ok(());
+            };
+
+            warn!("Verification of `{name}` failed at offset {offset}");
+            self.file_delete(name).await?;
+            if attempt == retries {
+                return Err(Error::VerifyMismatch {
+                    chunk: offset / FILE_WRITE_CHUNK_SIZE,
+                    offset,
+                });
+            }
+            attempt += 1;
+        }
+    }
+
+    /// Write the provided data to an open file in chunks
+    async fn upload_to(&self, handle: &FileHandle, data: &[u8]) -> Result<()> {
+        for chunk in data.chunks(FILE_WRITE_CHUNK_SIZE) {
+            let written = self.file_write(handle, chunk).await?;
+            if usize::from(written) != chunk.len() {
+                return Err(Error::Write);
+            }
+        }
+        Ok(())
+    }
+
     /// Search for a module matching the specified pattern and return a
     /// handle to the search state
     pub async fn module_find_first(
@@ -682,6 +888,60 @@ impl Nxt {
         self.send(&pkt, true).await
     }
 
+    /// List every module in the firmware. The module IDs are cached for
+    /// use by [`Nxt::read_field`] and [`Nxt::write_field`].
+    pub async fn list_modules(&self) -> Result<Vec<ModuleInfo>> {
+        let mut handle = match self.module_find_first(ALL_FILES).await {
+            Ok(handle) => handle,
+            Err(Error::Device(DeviceError::ModuleNotFound)) => {
+                return Ok(Vec::new())
+            }
+            Err(e) => return Err(e),
+        };
+
+        let mut modules = Vec::new();
+        let res = loop {
+            modules.push(ModuleInfo {
+                name: handle.name.clone(),
+                id: handle.id,
+                len: handle.len,
+                iomap_len: handle.iomap_len,
+            });
+            match self.module_find_next(&handle).await {
+                Ok(next) => handle = next,
+                Err(Error::Device(DeviceError::ModuleNotFound)) => {
+                    break Ok(modules)
+                }
+                Err(e) => break Err(e),
+            }
+        };
+
+        // as with file searches, the handle may already be released
+        let _ = self.module_close(&handle).await;
+
+        let modules = res?;
+        self.module_ids.lock().unwrap().extend(
+            modules
+                .iter()
+                .map(|module| (module.name.clone(), module.id)),
+        );
+        Ok(modules)
+    }
+
+    /// Look up the ID of the named module, listing the modules on the
+    /// brick the first time an ID is needed
+    pub async fn module_id(&self, name: &str) -> Result<u32> {
+        if let Some(&id) = self.module_ids.lock().unwrap().get(name) {
+            return Ok(id);
+        }
+        self.list_modules()
+            .await?
+            .into_iter()
+            .find(|module| module.name == name)
+            .map(|module| module.id)
+            .ok_or(Error::Device(DeviceError::ModuleNotFound))
+    }
+
     /// Read `count` bytes from the IO map belonging to the specified
     /// module at the given offset
     pub async fn read_io_map(
@@ -720,8 +980,8 @@ impl Nxt {
     }
 
     /// Enter firmware update mode - warning, this is not recoverable
-    /// without loading new firmware (not currently supported by this
-    /// crate)
+    /// without loading new firmware, which can be done with
+    /// [`samba::Samba`]
     pub async fn boot(&self, sure: bool) -> Result<Vec<u8>> {
         if !sure {
             return Err(Error::Serialise(
@@ -818,3 +1078,119 @@ impl Nxt {
         self.send(&pkt, true).await
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use socket::mock::{self, Brick};
+
+    #[tokio::test]
+    async fn verified_upload_retries() {
+        let (nxt, state) = mock::connect(Brick::default()).await;
+        let data = (0..200).collect::<Vec<u8>>();
+
+        state.lock().unwrap().corrupt_writes = 1;
+        nxt.upload_file_verified("prog.rxe", &data, 1)
+            .await
+            .unwrap();
+        assert_eq!(state.lock().unwrap().files["prog.rxe"].data, data);
+
+        // every chunk of both attempts
+        state.lock().unwrap().corrupt_writes = 8;
+        let err = nxt
+            .upload_file_verified("other.rxe", &data, 1)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            Error::VerifyMismatch {
+                chunk: 0,
+                offset: 0
+            }
+        ));
+        assert!(!state.lock().unwrap().files.contains_key("other.rxe"));
+    }
+
+    #[tokio::test]
+    async fn verified_upload_checks_space() {
+        let (nxt, state) = mock::connect(Brick::default()).await;
+        state.lock().unwrap().flash = 10;
+        let err = nxt
+            .upload_file_verified("big.rxe", &[0; 11], 0)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            Error::InsufficientFlash {
+                required: 11,
+                available: 10
+            }
+        ));
+    }
+
+    #[test]
+    fn mismatch_offset() {
+        assert_eq!(first_mismatch(b"abc", b"abc"), None);
+        assert_eq!(first_mismatch(b"abc", b"abd"), Some(2));
+        assert_eq!(first_mismatch(b"abc", b"ab"), Some(2));
+    }
+
+    #[tokio::test]
+    async fn list_modules_caches_ids() {
+        let mut brick = Brick::default();
+        brick.add_iomap(iomap::loader::MODULE, 8);
+        // a firmware which numbers its modules differently
+        let ui = iomap::Module {
+            id: 0x00ff_0001,
+            ..iomap::ui::MODULE
+        };
+        brick.add_iomap(ui, 64);
+        let (nxt, state) = mock::connect(brick).await;
+
+        let modules = nxt.list_modules().await.unwrap();
+        let names = modules.iter().map(|m| m.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, ["Loader.mod", "Ui.mod"]);
+        assert_eq!(modules[1].iomap_len, 64);
+        assert_eq!(state.lock().unwrap().handles_open(), 0);
+
+        nxt.write_field(iomap::ui::VOLUME, &2).await.unwrap();
+        assert_eq!(state.lock().unwrap().iomaps[&ui.id][36], 2);
+
+        // IDs are served from the cache once known
+        state.lock().unwrap().modules.clear();
+        assert_eq!(nxt.read_field(iomap::ui::VOLUME).await.unwrap(), 2);
+        assert_eq!(nxt.module_id("Ui.mod").await.unwrap(), ui.id);
+        nxt.module_id("Sound.mod").await.unwrap_err();
+    }
+
+    #[tokio::test]
+    async fn draw_on_display() {
+        let mut brick = Brick::default();
+        brick.add_iomap(iomap::display::MODULE, 1719);
+        brick.add_iomap(iomap::ui::MODULE, 64);
+        let (nxt, state) = mock::connect(brick).await;
+
+        let mut raster = [[0; DISPLAY_WIDTH]; DISPLAY_HEIGHT];
+        raster[0][0] = 1;
+        raster[9][1] = 1;
+        nxt.draw_raster(&raster).await.unwrap();
+        let data = nxt.get_display_data().await.unwrap();
+        assert_eq!(data[0], 0x01);
+        assert_eq!(data[DISPLAY_WIDTH + 1], 0x02);
+        assert_eq!(system::display_data_to_raster(&data), raster);
+
+        let ui_flags = |state: &Arc<Mutex<Brick>>| {
+            state.lock().unwrap().iomaps[&iomap::ui::MODULE.id][26]
+        };
+        state
+            .lock()
+            .unwrap()
+            .iomaps
+            .get_mut(&iomap::ui::MODULE.id)
+            .unwrap()[26] = iomap::ui::FLAGS_ENABLE_STATUS_UPDATE;
+        nxt.set_ui_redraw(false).await.unwrap();
+        assert_eq!(ui_flags(&state), 0);
+        nxt.set_ui_redraw(true).await.unwrap();
+        assert_eq!(ui_flags(&state), 0x89);
+    }
+}
@@ -0,0 +1,378 @@
+//! Typed messages for the brick's mailboxes, in the formats used by the
+//! NXT-G messaging blocks and the NXC `SendRemote*`/`ReceiveRemote*`
+//! functions.
+//!
+//! On the wire every message is null terminated, which
+//! [`Nxt::message_write`] adds and [`Message::decode`] strips. Text is
+//! sent as is, numbers as 32-bit little-endian signed integers and
+//! booleans as a single byte.
+//!
+//! Programs on the brick reply to the host through the response inboxes,
+//! which [`Nxt::subscribe`] polls.
+
+use crate::{
+    poll::{poll_stream, Poller},
+    DeviceError, Error, Nxt, Result, MAX_INBOX_ID, MAX_MESSAGE_LEN,
+};
+use futures::Stream;
+use std::{collections::VecDeque, ops::RangeInclusive, time::Duration};
+use tokio::time::Interval;
+
+/// Inboxes in which programs on the brick leave messages for the host,
+/// e.g. with the NXC `SendResponse*` functions
+pub const RESPONSE_INBOXES: RangeInclusive<u8> = 10..=19;
+
+/// Kind of value held by a message, needed to decode it
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum MessageKind {
+    /// Text
+    Text,
+    /// 32-bit signed number
+    Number,
+    /// Logic value
+    Bool,
+}
+
+/// A message to or from a mailbox
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Message {
+    /// Text, which must not contain null characters
+    Text(String),
+    /// 32-bit signed number
+    Number(i32),
+    /// Logic value
+    Bool(bool),
+}
+
+impl Message {
+    /// Kind of value held by the message
+    #[must_use]
+    pub const fn kind(&self) -> MessageKind {
+        match self {
+            Self::Text(_) => MessageKind::Text,
+            Self::Number(_) => MessageKind::Number,
+            Self::Bool(_) => MessageKind::Bool,
+        }
+    }
+
+    /// Encode the message, without the null terminator
+    pub fn encode(&self) -> Result<Vec<u8>> {
+        let data = match self {
+            Self::Text(text) => {
+                if text.contains('\0') {
+                    return Err(Error::Serialise(
+                        "Message text cannot contain null characters",
+                    ));
+                }
+                text.as_bytes().to_vec()
+            }
+            Self::Number(num) => num.to_le_bytes().to_vec(),
+            Self::Bool(val) => vec![u8::from(*val)],
+        };
+        check_message_len(data.len())?;
+        Ok(data)
+    }
+
+    /// Decode a message as read from a mailbox, with or without its null
+    /// terminator
+    pub fn decode(data: &[u8], kind: MessageKind) -> Result<Self> {
+        // numbers and `false` can end in a zero byte themselves, so the
+        // terminator is only stripped from data one byte too long
+        let strip = |len: usize| match data {
+            [value @ .., 0] if value.len() == len => value,
+            _ => data,
+        };
+        match kind {
+            MessageKind::Text => {
+                let data = data.strip_suffix(&[0]).unwrap_or(data);
+                Ok(Self::Text(String::from_utf8(data.to_vec())?))
+            }
+            MessageKind::Number => {
+                let bytes = strip(4).try_into().map_err(|_| {
+                    Error::Parse("Number message must be 4 bytes")
+                })?;
+                Ok(Self::Number(i32::from_le_bytes(bytes)))
+            }
+            MessageKind::Bool => match strip(1) {
+                [val] => Ok(Self::Bool(*val != 0)),
+                _ => Err(Error::Parse("Bool message must be 1 byte")),
+            },
+        }
+    }
+}
+
+impl From<&str> for Message {
+    fn from(text: &str) -> Self {
+        Self::Text(text.into())
+    }
+}
+
+impl From<String> for Message {
+    fn from(text: String) -> Self {
+        Self::Text(text)
+    }
+}
+
+impl From<i32> for Message {
+    fn from(num: i32) -> Self {
+        Self::Number(num)
+    }
+}
+
+impl From<bool> for Message {
+    fn from(val: bool) -> Self {
+        Self::Bool(val)
+    }
+}
+
+/// A message received from a response inbox
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InboxMessage {
+    /// Inbox the message was read from
+    pub inbox: u8,
+    /// The decoded message
+    pub message: Message,
+}
+
+/// State carried between messages of [`Nxt::subscribe`]
+struct Subscription {
+    /// Connection to the brick
+    nxt: Nxt,
+    /// Inboxes to poll, with the kind of message expected in each
+    inboxes: Vec<(u8, MessageKind)>,
+    /// Messages read but not yet yielded
+    ready: VecDeque<InboxMessage>,
+}
+
+impl Poller for Subscription {
+    type Item = InboxMessage;
+
+    /// Wait for the next message in any of the inboxes
+    async fn next(&mut self, interval: &mut Interval) -> Result<InboxMessage> {
+        loop {
+            if let Some(message) = self.ready.pop_front() {
+                return Ok(message);
+            }
+            interval.tick().await;
+            for &(inbox, kind) in &self.inboxes {
+                loop {
+                    match self.nxt.read_message(inbox, kind).await {
+                        Ok(message) => {
+                            self.ready
+                                .push_back(InboxMessage { inbox, message });
+                        }
+                        Err(Error::Device(DeviceError::QueueEmpty)) => break,
+                        Err(e) => return Err(e),
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Check that an inbox ID is in range
+pub(crate) const fn check_inbox(inbox: u8) -> Result<()> {
+    if inbox > MAX_INBOX_ID {
+        Err(Error::InvalidInbox(inbox))
+    } else {
+        Ok(())
+    }
+}
+
+/// Check that a message, excluding its null terminator, fits in a
+/// packet
+pub(crate) const fn check_message_len(len: usize) -> Result<()> {
+    if len > MAX_MESSAGE_LEN {
+        Err(Error::MessageTooLong(len))
+    } else {
+        Ok(())
+    }
+}
+
+impl Nxt {
+    /// Encode a message and write it to the specified inbox
+    pub async fn send_message(
+        &self,
+        inbox: u8,
+        message: impl Into<Message> + Send,
+    ) -> Result<()> {
+        let data = message.into().encode()?;
+        self.message_write(inbox, &data).await
+    }
+
+    /// Read a message of the given kind from the specified inbox,
+    /// removing it from the queue. Returns a
+    /// `DeviceError::QueueEmpty` error if there are no messages waiting.
+    pub async fn read_message(
+        &self,
+        inbox: u8,
+        kind: MessageKind,
+    ) -> Result<Message> {
+        let data = self.message_read(inbox, 0, true).await?;
+        Message::decode(&data, kind)
+    }
+
+    /// Poll the given response inboxes at the given interval, yielding
+    /// each message decoded as the kind given for its inbox. Each poll
+    /// empties the inboxes, and empty inboxes are skipped. Errors are
+    /// yielded without ending the stream; stop polling by dropping it.
+    ///
+    /// Returns an error if any inbox is not one of the
+    /// [`RESPONSE_INBOXES`]. See
+    /// [`MIN_POLL_INTERVAL`](crate::MIN_POLL_INTERVAL) for the shortest
+    /// interval and the runtime needed.
+    pub fn subscribe(
+        &self,
+        inboxes: &[(u8, MessageKind)],
+        interval: Duration,
+    ) -> Result<impl Stream<Item = Result<InboxMessage>> + Send + 'static> {
+        if inboxes
+            .iter()
+            .any(|(inbox, _)| !RESPONSE_INBOXES.contains(inbox))
+        {
+            return Err(Error::Serialise(
+                "Only response inboxes (10 to 19) can be subscribed to",
+            ));
+        }
+        let subscription = Subscription {
+            nxt: self.clone(),
+            inboxes: inboxes.to_vec(),
+            ready: VecDeque::new(),
+        };
+        Ok(poll_stream(interval, subscription))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::socket::mock::{self, Brick};
+    use futures::StreamExt;
+    use tokio::time;
+
+    #[test]
+    fn encode_decode() {
+        assert_eq!(Message::from("hi").encode().unwrap(), b"hi");
+        assert_eq!(
+            Message::from(-2).encode().unwrap(),
+            [0xfe, 0xff, 0xff, 0xff]
+        );
+        assert_eq!(Message::from(true).encode().unwrap(), [1]);
+        Message::from("a\0b").encode().unwrap_err();
+        assert!(matches!(
+            Message::from("x".repeat(59)).encode(),
+            Err(Error::MessageTooLong(59))
+        ));
+
+        assert_eq!(
+            Message::decode(b"hi\0", MessageKind::Text).unwrap(),
+            Message::from("hi")
+        );
+        assert_eq!(
+            Message::decode(&[7, 0, 0, 0, 0], MessageKind::Number).unwrap(),
+            Message::Number(7)
+        );
+        assert_eq!(
+            Message::decode(&[0, 0], MessageKind::Bool).unwrap(),
+            Message::Bool(false)
+        );
+        Message::decode(&[1, 2, 0], MessageKind::Number).unwrap_err();
+        Message::decode(&[1, 2], MessageKind::Bool).unwrap_err();
+
+        // values ending in a zero byte survive a round trip
+        for (message, kind) in [
+            (Message::Number(7), MessageKind::Number),
+            (Message::Bool(false), MessageKind::Bool),
+            (Message::from(""), MessageKind::Text),
+        ] {
+            let data = message.encode().unwrap();
+            assert_eq!(Message::decode(&data, kind).unwrap(), message);
+        }
+    }
+
+    #[tokio::test]
+    async fn send_read() {
+        let (nxt, state) = mock::connect(Brick::default()).await;
+
+        nxt.send_message(3, 1234).await.unwrap();
+        assert_eq!(
+            state.lock().unwrap().mailboxes[&3][0],
+            [0xd2, 0x04, 0, 0, 0]
+        );
+        assert!(matches!(
+            nxt.send_message(MAX_INBOX_ID + 1, true).await,
+            Err(Error::InvalidInbox(20))
+        ));
+
+        state
+            .lock()
+            .unwrap()
+            .mailboxes
+            .entry(12)
+            .or_default()
+            .push_back(b"done\0".to_vec());
+        assert_eq!(
+            nxt.read_message(12, MessageKind::Text).await.unwrap(),
+            Message::from("done")
+        );
+        nxt.read_message(12, MessageKind::Text).await.unwrap_err();
+    }
+
+    #[tokio::test]
+    async fn subscribe() {
+        let (nxt, state) = mock::connect(Brick::default()).await;
+        let respond = |inbox: u8, data: &[u8]| {
+            state
+                .lock()
+                .unwrap()
+                .mailboxes
+                .entry(inbox)
+                .or_default()
+                .push_back(data.to_vec());
+        };
+
+        assert!(nxt
+            .subscribe(&[(3, MessageKind::Text)], Duration::from_millis(1))
+            .is_err());
+        let mut messages = Box::pin(
+            nxt.subscribe(
+                &[(11, MessageKind::Text), (15, MessageKind::Number)],
+                Duration::from_millis(1),
+            )
+            .unwrap(),
+        );
+
+        respond(11, b"one\0");
+        respond(15, &[2, 0, 0, 0, 0]);
+        respond(11, b"three\0");
+        // not subscribed
+        respond(12, b"ignored\0");
+        let mut received = Vec::new();
+        for _ in 0..3 {
+            received.push(messages.next().await.unwrap().unwrap());
+        }
+        assert_eq!(
+            received,
+            [
+                InboxMessage {
+                    inbox: 11,
+                    message: Message::from("one"),
+                },
+                InboxMessage {
+                    inbox: 11,
+                    message: Message::from("three"),
+                },
+                InboxMessage {
+                    inbox: 15,
+                    message: Message::Number(2),
+                },
+            ]
+        );
+
+        // empty inboxes are not an error
+        time::timeout(Duration::from_millis(20), messages.next())
+            .await
+            .unwrap_err();
+        assert_eq!(state.lock().unwrap().mailboxes[&12].len(), 1);
+    }
+}
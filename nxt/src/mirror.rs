@@ -0,0 +1,149 @@
+//! Streaming the contents of the brick's display, e.g. to mirror it in
+//! a GUI.
+//!
+//! Each frame is read from the display module's iomap in the largest
+//! chunks a reply packet can hold. The NXT doesn't track which parts of
+//! the screen have changed, so every frame reads the whole frame buffer,
+//! but the display flags are checked first: while refreshing is disabled
+//! the LCD can't change, so nothing more is read. Frames identical to the
+//! previous one are not yielded.
+
+use crate::{
+    iomap::display,
+    poll::{poll_stream, Poller},
+    system::{display_data_to_raster, DisplayRaster},
+    Nxt, Result,
+};
+use futures::Stream;
+use std::time::{Duration, Instant};
+use tokio::time::Interval;
+
+/// A frame captured from the display
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Frame {
+    /// Contents of the screen
+    pub raster: DisplayRaster,
+    /// When the frame finished being read
+    pub timestamp: Instant,
+    /// Whether the popup frame buffer was being shown rather than the
+    /// normal one
+    pub popup: bool,
+}
+
+/// State carried between frames of [`Nxt::mirror_display`]
+struct Mirror {
+    /// Connection to the brick
+    nxt: Nxt,
+    /// Display flags and screen contents of the last frame
+    last: Option<(u8, DisplayRaster)>,
+}
+
+impl Poller for Mirror {
+    type Item = Frame;
+
+    /// Wait for the next frame which differs from the last one
+    async fn next(&mut self, interval: &mut Interval) -> Result<Frame> {
+        loop {
+            interval.tick().await;
+            let flags = self.nxt.read_field(display::FLAGS).await?;
+            if flags & display::FLAG_REFRESH_DISABLED != 0
+                && self.last.is_some_and(|(last, _)| last == flags)
+            {
+                continue;
+            }
+
+            let popup = flags & display::FLAG_POPUP != 0;
+            let buffer = if popup {
+                display::POPUP
+            } else {
+                display::NORMAL
+            };
+            let data = self.nxt.read_field(buffer).await?;
+            // the field is exactly one screen long
+            let raster = display_data_to_raster(&data.try_into().unwrap());
+            let timestamp = Instant::now();
+
+            let changed = self.last.is_none_or(|(_, last)| last != raster);
+            self.last = Some((flags, raster));
+            if changed {
+                return Ok(Frame {
+                    raster,
+                    timestamp,
+                    popup,
+                });
+            }
+        }
+    }
+}
+
+impl Nxt {
+    /// Poll the display at the given interval, yielding a frame each
+    /// time its contents change. If reading the display falls behind,
+    /// missed polls are skipped rather than made up. Errors are yielded
+    /// without ending the stream; stop polling by dropping it.
+    ///
+    /// See [`MIN_POLL_INTERVAL`](crate::MIN_POLL_INTERVAL) for the
+    /// shortest interval and the runtime needed.
+    pub fn mirror_display(
+        &self,
+        interval: Duration,
+    ) -> impl Stream<Item = Result<Frame>> + Send + 'static {
+        let mirror = Mirror {
+            nxt: self.clone(),
+            last: None,
+        };
+        poll_stream(interval, mirror)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        socket::mock::{self, Brick},
+        system::raster_to_display_data,
+    };
+    use futures::StreamExt;
+    use tokio::time;
+
+    #[tokio::test]
+    async fn mirror() {
+        let mut brick = Brick::default();
+        brick.add_iomap(display::MODULE, 1719);
+        let (nxt, state) = mock::connect(brick).await;
+        let set_screen = |offset: usize, raster: &DisplayRaster, flags: u8| {
+            let data = raster_to_display_data(raster);
+            let mut state = state.lock().unwrap();
+            let iomap = state.iomaps.get_mut(&display::MODULE.id).unwrap();
+            iomap[usize::from(display::FLAGS.offset)] = flags;
+            iomap[offset..offset + data.len()].copy_from_slice(&data);
+            drop(state);
+        };
+        let normal = usize::from(display::NORMAL.offset);
+        let popup = usize::from(display::POPUP.offset);
+
+        let mut raster = [[0; 100]; 64];
+        raster[10][20] = 1;
+        set_screen(normal, &raster, display::FLAG_ON);
+
+        // a zero interval is raised to the minimum rather than panicking
+        let mut frames = Box::pin(nxt.mirror_display(Duration::ZERO));
+        let first = frames.next().await.unwrap().unwrap();
+        assert_eq!(first.raster, raster);
+        assert!(!first.popup);
+
+        // the popup buffer is shown when its flag is set
+        raster[63][99] = 1;
+        set_screen(popup, &raster, display::FLAG_ON | display::FLAG_POPUP);
+        let second = frames.next().await.unwrap().unwrap();
+        assert_eq!(second.raster, raster);
+        assert!(second.popup);
+        assert!(second.timestamp > first.timestamp);
+
+        // an unchanged screen yields nothing
+        set_screen(normal, &raster, display::FLAG_ON);
+        time::timeout(Duration::from_millis(20), frames.next())
+            .await
+            .unwrap_err();
+    }
+}
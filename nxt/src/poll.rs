@@ -0,0 +1,34 @@
+//! Shared plumbing for the streams which poll the brick on a timer
+
+use crate::{Result, MIN_POLL_INTERVAL};
+use futures::{stream, Stream};
+use std::{future::Future, time::Duration};
+use tokio::time::{self, Interval, MissedTickBehavior};
+
+/// State of a stream which polls the brick on a timer
+pub trait Poller: Send + 'static {
+    /// Type of the items yielded
+    type Item: Send + 'static;
+
+    /// Wait for the next item, ticking `interval` before each poll of
+    /// the brick
+    fn next(
+        &mut self,
+        interval: &mut Interval,
+    ) -> impl Future<Output = Result<Self::Item>> + Send;
+}
+
+/// Build a stream which polls at the given interval, at least
+/// [`MIN_POLL_INTERVAL`]. Missed polls are skipped rather than made up,
+/// and errors are yielded without ending the stream.
+pub fn poll_stream<P: Poller>(
+    interval: Duration,
+    poller: P,
+) -> impl Stream<Item = Result<P::Item>> + Send + 'static {
+    let mut interval = time::interval(interval.max(MIN_POLL_INTERVAL));
+    interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+    stream::unfold((poller, interval), |(mut poller, mut interval)| async {
+        let item = poller.next(&mut interval).await;
+        Some((item, (poller, interval)))
+    })
+}
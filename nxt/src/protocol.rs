@@ -93,82 +93,121 @@ impl TryFrom<u8> for Opcode {
     }
 }
 
-#[derive(Copy, Clone, Debug, FromPrimitive, thiserror::Error)]
+/// Error status returned by the brick in reply to a request
+#[derive(
+    Copy, Clone, Debug, PartialEq, Eq, FromPrimitive, thiserror::Error,
+)]
 #[repr(u8)]
-#[allow(clippy::missing_docs_in_private_items)]
 pub enum DeviceError {
+    /// Success
     #[error("None")]
     None = 0x00,
+    /// A communication transaction is still in progress
     #[error("pending communication transaction in progress")]
     InProgress = 0x20,
+    /// The mailbox queue is empty
     #[error("specified mailbox queue is empty")]
     QueueEmpty = 0x40,
+    /// All file handles are in use
     #[error("no more handles")]
     NoMoreHandles = 0x81,
+    /// There is not enough flash for the file
     #[error("no space")]
     NoSpace = 0x82,
+    /// No more files match the search
     #[error("no more files")]
     NoMoreFiles = 0x83,
+    /// The end of the file was expected
     #[error("end of file expected")]
     EofExpected = 0x84,
+    /// The end of the file was reached
     #[error("end of file")]
     Eof = 0x85,
+    /// The file is not a linear file
     #[error("not a linear file")]
     NotALinearFile = 0x86,
+    /// The file does not exist
     #[error("file not found")]
     FileNotFound = 0x87,
+    /// The handle has already been closed
     #[error("handle already closed")]
     HandleAlreadyClosed = 0x88,
+    /// There is not enough contiguous flash for a linear file
     #[error("no linear space")]
     NoLinearSpace = 0x89,
+    /// An unspecified error occurred
     #[error("undefined error")]
     Undefined = 0x8A,
+    /// The file is open elsewhere
     #[error("file is busy")]
     FileBusy = 0x8B,
+    /// No write buffers are available
     #[error("no write buffers")]
     NoWriteBuffers = 0x8C,
+    /// The file cannot be appended to
     #[error("append not possible")]
     AppendNotPossible = 0x8D,
+    /// The file has reached its declared length
     #[error("file is full")]
     FileIsFull = 0x8E,
+    /// A file with the name already exists
     #[error("file exists")]
     FileExists = 0x8F,
+    /// No module matches the name
     #[error("module not found")]
     ModuleNotFound = 0x90,
+    /// An offset or length is out of bounds
     #[error("out of bounds")]
     OutOfBounds = 0x91,
+    /// The file name is not valid
     #[error("File does not exist")]
     IllegalName = 0x92,
+    /// The handle is not valid
     #[error("illegal handle")]
     IllegalHandle = 0x93,
+    /// The request failed, e.g. the file was not found
     #[error("request failed (i.e. specified file not found)")]
     RequestFailed = 0xBD,
+    /// The opcode is not recognised
     #[error("unknown command opcode")]
     UnknownCommand = 0xBE,
+    /// The packet is malformed
     #[error("insane packet (?)")]
     InsanePacket = 0xBF,
+    /// The request contains out of range values
     #[error("data contains out-of-range values")]
     ValueOutOfRange = 0xC0,
+    /// A communication bus error occurred
     #[error("communication bus error")]
     BusError = 0xDD,
+    /// The communication buffer is full
     #[error("no free memory in communication buffer")]
     BufferFull = 0xDE,
+    /// The channel or connection is not valid
     #[error("specified channel/connection is not valid")]
     InvalidChannel = 0xDF,
+    /// The channel or connection is not configured or is busy
     #[error("specified channel/connection not configured or busy")]
     UnconfiguredChannel = 0xE0,
+    /// No program is running
     #[error("No active program")]
     NoActiveProgram = 0xEC,
+    /// The size is not valid
     #[error("illegal size specified")]
     IllegalSize = 0xED,
+    /// The mailbox queue ID is not valid
     #[error("illegal mailbox queue ID specified")]
     IllegalQueueId = 0xEE,
+    /// A field of a structure which does not exist was accessed
     #[error("attempted to access invalid field of a structure")]
     InvalidField = 0xEF,
+    /// The input or output port is not valid
     #[error("bad input or output specified")]
     BadInputOrOutput = 0xF0,
+    /// There is not enough memory available
     #[error("insufficient memory available")]
     InsufficientMemory = 0xFB,
+    /// The arguments are not valid
     #[error("bad arguments")]
     BadArguments = 0xFF,
 }
@@ -0,0 +1,226 @@
+//! Sending mailbox messages to slave bricks connected over Bluetooth to
+//! a master brick on USB, and reading the master's Bluetooth
+//! connections from the comm module's iomap.
+//!
+//! Direct commands only reach the brick they are sent to, so messages
+//! for the slaves are forwarded by a relay program running on the
+//! master, which sends them on with the firmware's Bluetooth messaging.
+//!
+//! # Protocol
+//!
+//! The host writes a text message to the relay inbox on the master,
+//! made up of:
+//!
+//! 1. The connection number of the slave, one digit from 1 to 3
+//! 2. The slave's inbox, two digits from `00` to `19`
+//! 3. The kind of message: `T` for text, `N` for a number or `B` for a
+//!    boolean
+//! 4. The value: the text, the number in decimal, or `0` or `1`
+//!
+//! For example, `205N-42` sends the number -42 to inbox 5 of the slave
+//! on connection 2.
+//!
+//! In NXC, relaying messages written to inbox 9:
+//!
+//! ```c
+//! task main() {
+//!     string msg, kind, value;
+//!     byte conn, inbox;
+//!     while (true) {
+//!         if (ReceiveRemoteString(MAILBOX10, true, msg) == NO_ERR) {
+//!             conn = StrToNum(SubStr(msg, 0, 1));
+//!             inbox = StrToNum(SubStr(msg, 1, 2));
+//!             kind = SubStr(msg, 3, 1);
+//!             value = SubStr(msg, 4, StrLen(msg) - 4);
+//!             until (RemoteConnectionIdle(conn));
+//!             if (kind == "N") {
+//!                 SendRemoteNumber(conn, inbox, StrToNum(value));
+//!             } else if (kind == "B") {
+//!                 SendRemoteBool(conn, inbox, value == "1");
+//!             } else {
+//!                 SendRemoteString(conn, inbox, value);
+//!             }
+//!         }
+//!         Wait(10);
+//!     }
+//! }
+//! ```
+
+use crate::{
+    iomap::{comm, Field, FieldValue},
+    mailbox::{check_inbox, Message},
+    Error, Nxt, Result,
+};
+
+/// Connection numbers of the slaves of a master brick
+pub const SLAVE_CONNECTIONS: std::ops::RangeInclusive<u8> =
+    1..=comm::MAX_CONNECTIONS - 1;
+
+/// A Bluetooth connection of the brick
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Connection {
+    /// Connection number; 0 is the connection to this brick's master,
+    /// 1 to 3 are its slaves
+    pub number: u8,
+    /// Name of the connected device
+    pub name: String,
+    /// Class of the connected device
+    pub class: u32,
+    /// Bluetooth address of the connected device
+    pub addr: [u8; 6],
+    /// State of the data stream on the connection
+    pub stream_status: u8,
+    /// Link quality, from 0 to 255
+    pub link_quality: u8,
+}
+
+impl Nxt {
+    /// Read the brick's Bluetooth connection with the given number (0
+    /// to 3), or `None` if it isn't in use
+    pub async fn get_connection(
+        &self,
+        number: u8,
+    ) -> Result<Option<Connection>> {
+        let Some(idx) = comm::ConnectionIndex::new(number) else {
+            return Err(Error::Serialise("Connection must be from 0 to 3"));
+        };
+        // read the whole entry of the connection table at once
+        let start = comm::connection_name(idx).offset;
+        let last = comm::connection_link_quality(idx);
+        let end = last.offset + last.len;
+        let id = self.module_id(comm::MODULE.name).await?;
+        let data = self.read_io_map_range(id, start, end - start).await?;
+        let name = decode_field(&data, start, comm::connection_name(idx))?;
+        if name.is_empty() {
+            return Ok(None);
+        }
+        let addr = decode_field(&data, start, comm::connection_addr(idx))?;
+        Ok(Some(Connection {
+            number,
+            name,
+            class: decode_field(&data, start, comm::connection_class(idx))?,
+            addr: addr[..6].try_into().unwrap(),
+            stream_status: decode_field(
+                &data,
+                start,
+                comm::connection_stream_status(idx),
+            )?,
+            link_quality: decode_field(
+                &data,
+                start,
+                comm::connection_link_quality(idx),
+            )?,
+        }))
+    }
+
+    /// Read all of the brick's Bluetooth connections which are in use
+    pub async fn get_connections(&self) -> Result<Vec<Connection>> {
+        let mut connections = Vec::new();
+        for number in 0..comm::MAX_CONNECTIONS {
+            connections.extend(self.get_connection(number).await?);
+        }
+        Ok(connections)
+    }
+
+    /// Send a message to an inbox of the slave on the given connection
+    /// (1 to 3), through the relay program described in the
+    /// [module docs](self) reading `relay_inbox` on this brick. The
+    /// message is delivered once the relay program has forwarded it.
+    pub async fn relay_message(
+        &self,
+        relay_inbox: u8,
+        connection: u8,
+        inbox: u8,
+        message: impl Into<Message> + Send,
+    ) -> Result<()> {
+        if !SLAVE_CONNECTIONS.contains(&connection) {
+            return Err(Error::Serialise("Slave connection must be 1 to 3"));
+        }
+        check_inbox(inbox)?;
+        let request = encode_relay(connection, inbox, &message.into())?;
+        self.send_message(relay_inbox, request).await
+    }
+}
+
+/// Decode a field from data read from the iomap starting at `start`
+fn decode_field<T: FieldValue>(
+    data: &[u8],
+    start: u16,
+    field: Field<T>,
+) -> Result<T> {
+    let offset = usize::from(field.offset - start);
+    T::decode(&data[offset..offset + usize::from(field.len)])
+}
+
+/// Encode a message for the relay program
+fn encode_relay(
+    connection: u8,
+    inbox: u8,
+    message: &Message,
+) -> Result<String> {
+    let (kind, value) = match message {
+        Message::Text(text) => {
+            // check for nulls
+            message.encode()?;
+            ('T', text.clone())
+        }
+        Message::Number(num) => ('N', num.to_string()),
+        Message::Bool(val) => ('B', u8::from(*val).to_string()),
+    };
+    Ok(format!("{connection}{inbox:02}{kind}{value}"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::socket::mock::{self, Brick};
+
+    #[tokio::test]
+    async fn relay() {
+        let mut brick = Brick::default();
+        brick.add_iomap(comm::MODULE, 1887);
+        let (nxt, state) = mock::connect(brick).await;
+
+        assert_eq!(nxt.get_connections().await.unwrap(), []);
+        let conn = comm::ConnectionIndex::new(2).unwrap();
+        let offset = usize::from(comm::connection_name(conn).offset);
+        let mut entry = vec![0; 47];
+        entry[..6].copy_from_slice(b"slave\0");
+        entry[16..20].copy_from_slice(&0x0804u32.to_le_bytes());
+        entry[36..42].copy_from_slice(&[1, 2, 3, 4, 5, 6]);
+        entry[44] = 1;
+        entry[45] = 200;
+        state
+            .lock()
+            .unwrap()
+            .iomaps
+            .get_mut(&comm::MODULE.id)
+            .unwrap()[offset..offset + entry.len()]
+            .copy_from_slice(&entry);
+        assert_eq!(
+            nxt.get_connections().await.unwrap(),
+            [Connection {
+                number: 2,
+                name: "slave".into(),
+                class: 0x0804,
+                addr: [1, 2, 3, 4, 5, 6],
+                stream_status: 1,
+                link_quality: 200,
+            }]
+        );
+        nxt.get_connection(4).await.unwrap_err();
+
+        nxt.relay_message(9, 2, 5, -42).await.unwrap();
+        nxt.relay_message(9, 1, 12, "hi").await.unwrap();
+        nxt.relay_message(9, 3, 0, true).await.unwrap();
+        assert_eq!(
+            state.lock().unwrap().mailboxes[&9],
+            [&b"205N-42\0"[..], b"112Thi\0", b"300B1\0"]
+        );
+        nxt.relay_message(9, 0, 5, 1).await.unwrap_err();
+        nxt.relay_message(9, 1, 20, 1).await.unwrap_err();
+        nxt.relay_message(9, 1, 5, "x".repeat(55))
+            .await
+            .unwrap_err();
+    }
+}
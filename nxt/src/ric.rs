@@ -0,0 +1,814 @@
+//! Parsing, writing and rendering of the NXT `.ric` graphics format.
+//!
+//! A RIC file is a sequence of drawing opcodes, each prefixed with its
+//! size in bytes (excluding the size field itself). All fields are
+//! 16-bit little endian. Drawing coordinates follow the firmware
+//! convention of the origin being at the bottom-left of the screen.
+
+use crate::{
+    draw::{BlitOp, Draw},
+    font,
+    system::DisplayRaster,
+    Error, Result, DISPLAY_HEIGHT, DISPLAY_WIDTH,
+};
+use std::collections::HashMap;
+
+/// Clear the whole screen before drawing
+pub const DRAW_OPT_CLEAR_WHOLE_SCREEN: u16 = 0x0001;
+/// Clear the screen, except for the status line, before drawing
+pub const DRAW_OPT_CLEAR_EXCEPT_STATUS_SCREEN: u16 = 0x0002;
+/// Draw in the background colour, i.e. clear pixels
+pub const DRAW_OPT_CLEAR_PIXELS: u16 = 0x0004;
+/// Mask of the bits selecting how sprite pixels are combined with the
+/// screen
+pub const DRAW_OPT_LOGICAL_OPS: u16 = 0x0018;
+/// Overwrite the screen with the sprite
+pub const DRAW_OPT_LOGICAL_COPY: u16 = 0x0000;
+/// AND the sprite with the screen
+pub const DRAW_OPT_LOGICAL_AND: u16 = 0x0008;
+/// OR the sprite with the screen
+pub const DRAW_OPT_LOGICAL_OR: u16 = 0x0010;
+/// XOR the sprite with the screen
+pub const DRAW_OPT_LOGICAL_XOR: u16 = 0x0018;
+/// Fill rectangles and circles
+pub const DRAW_OPT_FILL_SHAPE: u16 = 0x0020;
+
+/// Height of the status line at the top of the screen
+const STATUS_HEIGHT: usize = 8;
+
+/// Opcode: image description
+const OP_DESCRIPTION: u16 = 0;
+/// Opcode: sprite data
+const OP_SPRITE: u16 = 1;
+/// Opcode: parameter variable map
+const OP_VARMAP: u16 = 2;
+/// Opcode: copy an area of a sprite to the screen
+const OP_COPYBITS: u16 = 3;
+/// Opcode: draw a pixel
+const OP_PIXEL: u16 = 4;
+/// Opcode: draw a line
+const OP_LINE: u16 = 5;
+/// Opcode: draw a rectangle
+const OP_RECTANGLE: u16 = 6;
+/// Opcode: draw a circle
+const OP_CIRCLE: u16 = 7;
+/// Opcode: draw a number
+const OP_NUMBOX: u16 = 8;
+
+/// A point on the screen, or within a sprite
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[allow(missing_docs)]
+pub struct Point {
+    pub x: i16,
+    pub y: i16,
+}
+
+/// A rectangular area on the screen, or within a sprite
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[allow(missing_docs)]
+pub struct Rect {
+    pub x: i16,
+    pub y: i16,
+    pub width: i16,
+    pub height: i16,
+}
+
+/// A 1-bit bitmap which can be drawn with [`Op::CopyBits`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Sprite {
+    /// Address used to refer to the sprite from `CopyBits`
+    pub addr: u16,
+    /// Number of rows
+    pub rows: u16,
+    /// Bytes per row; each byte holds 8 pixels, most significant bit
+    /// leftmost
+    pub row_bytes: u16,
+    /// Pixel data, top row first
+    pub data: Vec<u8>,
+}
+
+impl Sprite {
+    /// Build a sprite from row-major pixel data with one byte per
+    /// pixel, in the same format as a [`DisplayRaster`] row
+    pub fn from_bitmap(
+        addr: u16,
+        width: usize,
+        height: usize,
+        pixels: &[u8],
+    ) -> Result<Self> {
+        if pixels.len() != width * height {
+            return Err(Error::Serialise("Bitmap size mismatch"));
+        }
+        let row_bytes = width.div_ceil(8);
+        let mut data = vec![0; row_bytes * height];
+        for (idx, &pixel) in pixels.iter().enumerate() {
+            if pixel != 0 {
+                let (row, col) = (idx / width, idx % width);
+                data[row * row_bytes + col / 8] |= 0x80 >> (col % 8);
+            }
+        }
+        Ok(Self {
+            addr,
+            rows: height.try_into()?,
+            row_bytes: row_bytes.try_into()?,
+            data,
+        })
+    }
+
+    /// Width of the sprite in pixels
+    #[must_use]
+    pub const fn width(&self) -> u16 {
+        self.row_bytes * 8
+    }
+
+    /// Whether the pixel at the given position (from the top-left) is
+    /// set. Pixels outside the sprite are unset.
+    #[must_use]
+    pub fn pixel(&self, x: i32, y: i32) -> bool {
+        let (Ok(x), Ok(y)) = (usize::try_from(x), usize::try_from(y)) else {
+            return false;
+        };
+        let row_bytes = usize::from(self.row_bytes);
+        if x >= row_bytes * 8 || y >= usize::from(self.rows) {
+            return false;
+        }
+        self.data
+            .get(y * row_bytes + x / 8)
+            .is_some_and(|byte| byte & (0x80 >> (x % 8)) != 0)
+    }
+}
+
+/// A single drawing instruction from a RIC file
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Op {
+    /// Image description; informational only
+    Description {
+        /// Drawing options
+        options: u16,
+        /// Width of the image
+        width: u16,
+        /// Height of the image
+        height: u16,
+    },
+    /// Sprite data for later use by `CopyBits`
+    Sprite(Sprite),
+    /// Variable map, used to transform parameters passed in by the
+    /// calling program. Parameters are not evaluated when rendering.
+    VarMap {
+        /// Address of the map
+        addr: u16,
+        /// `(domain, range)` pairs
+        map: Vec<(u16, u16)>,
+    },
+    /// Copy an area of a sprite to the screen
+    CopyBits {
+        /// Drawing options
+        options: u16,
+        /// Address of the source sprite
+        addr: u16,
+        /// Area within the sprite, measured from its top-left corner
+        src: Rect,
+        /// Position of the bottom-left corner of the copied area
+        dest: Point,
+    },
+    /// Draw a single pixel
+    Pixel {
+        /// Drawing options
+        options: u16,
+        /// Position of the pixel
+        point: Point,
+        /// Unused
+        value: u16,
+    },
+    /// Draw a line
+    Line {
+        /// Drawing options
+        options: u16,
+        /// Start point
+        start: Point,
+        /// End point
+        end: Point,
+    },
+    /// Draw a rectangle, measured from its bottom-left corner
+    Rectangle {
+        /// Drawing options
+        options: u16,
+        /// Area of the rectangle
+        rect: Rect,
+    },
+    /// Draw a circle
+    Circle {
+        /// Drawing options
+        options: u16,
+        /// Centre of the circle
+        center: Point,
+        /// Radius of the circle
+        radius: i16,
+    },
+    /// Draw a number in the system font
+    Number {
+        /// Drawing options
+        options: u16,
+        /// Position of the bottom-left corner of the text
+        point: Point,
+        /// Number to draw
+        value: i16,
+    },
+    /// An opcode not understood by this crate, preserved verbatim
+    Unknown {
+        /// Opcode number
+        opcode: u16,
+        /// Raw arguments
+        args: Vec<u8>,
+    },
+}
+
+/// Helper for pulling little-endian words out of an opcode's arguments
+struct Args<'a> {
+    /// Remaining argument data
+    data: &'a [u8],
+}
+
+impl Args<'_> {
+    /// Read an unsigned word
+    fn u16(&mut self) -> Result<u16> {
+        let (word, rest) = self
+            .data
+            .split_first_chunk::<2>()
+            .ok_or(Error::Parse("RIC opcode too short"))?;
+        self.data = rest;
+        Ok(u16::from_le_bytes(*word))
+    }
+
+    /// Read a signed word
+    fn i16(&mut self) -> Result<i16> {
+        Ok(self.u16()?.cast_signed())
+    }
+
+    /// Read a point
+    fn point(&mut self) -> Result<Point> {
+        Ok(Point {
+            x: self.i16()?,
+            y: self.i16()?,
+        })
+    }
+
+    /// Read a rectangle
+    fn rect(&mut self) -> Result<Rect> {
+        Ok(Rect {
+            x: self.i16()?,
+            y: self.i16()?,
+            width: self.i16()?,
+            height: self.i16()?,
+        })
+    }
+}
+
+impl Op {
+    /// Parse the opcode with the given number from its arguments
+    fn parse(opcode: u16, data: &[u8]) -> Result<Self> {
+        let mut args = Args { data };
+        Ok(match opcode {
+            OP_DESCRIPTION => Self::Description {
+                options: args.u16()?,
+                width: args.u16()?,
+                height: args.u16()?,
+            },
+            OP_SPRITE => {
+                let addr = args.u16()?;
+                let rows = args.u16()?;
+                let row_bytes = args.u16()?;
+                let len = usize::from(rows) * usize::from(row_bytes);
+                let data = args
+                    .data
+                    .get(..len)
+                    .ok_or(Error::Parse("RIC sprite data too short"))?
+                    .to_vec();
+                Self::Sprite(Sprite {
+                    addr,
+                    rows,
+                    row_bytes,
+                    data,
+                })
+            }
+            OP_VARMAP => {
+                let addr = args.u16()?;
+                let count = args.u16()?;
+                let map = (0..count)
+                    .map(|_| Ok((args.u16()?, args.u16()?)))
+                    .collect::<Result<_>>()?;
+                Self::VarMap { addr, map }
+            }
+            OP_COPYBITS => Self::CopyBits {
+                options: args.u16()?,
+                addr: args.u16()?,
+                src: args.rect()?,
+                dest: args.point()?,
+            },
+            OP_PIXEL => Self::Pixel {
+                options: args.u16()?,
+                point: args.point()?,
+                value: args.u16()?,
+            },
+            OP_LINE => Self::Line {
+                options: args.u16()?,
+                start: args.point()?,
+                end: args.point()?,
+            },
+            OP_RECTANGLE => Self::Rectangle {
+                options: args.u16()?,
+                rect: args.rect()?,
+            },
+            OP_CIRCLE => Self::Circle {
+                options: args.u16()?,
+                center: args.point()?,
+                radius: args.i16()?,
+            },
+            OP_NUMBOX => Self::Number {
+                options: args.u16()?,
+                point: args.point()?,
+                value: args.i16()?,
+            },
+            _ => Self::Unknown {
+                opcode,
+                args: data.to_vec(),
+            },
+        })
+    }
+
+    /// Serialise the opcode number and arguments
+    fn serialise(&self) -> Result<(u16, Vec<u8>)> {
+        let mut args = Vec::new();
+        let mut push = |words: &[u16]| {
+            for word in words {
+                args.extend(word.to_le_bytes());
+            }
+        };
+        let point = |p: &Point| [p.x.cast_unsigned(), p.y.cast_unsigned()];
+        let rect =
+            |r: &Rect| [r.x, r.y, r.width, r.height].map(i16::cast_unsigned);
+
+        let opcode = match self {
+            Self::Description {
+                options,
+                width,
+                height,
+            } => {
+                push(&[*options, *width, *height]);
+                OP_DESCRIPTION
+            }
+            Self::Sprite(sprite) => {
+                push(&[sprite.addr, sprite.rows, sprite.row_bytes]);
+                args.extend(&sprite.data);
+                OP_SPRITE
+            }
+            Self::VarMap { addr, map } => {
+                push(&[*addr, u16::try_from(map.len())?]);
+                for (domain, range) in map {
+                    push(&[*domain, *range]);
+                }
+                OP_VARMAP
+            }
+            Self::CopyBits {
+                options,
+                addr,
+                src,
+                dest,
+            } => {
+                push(&[*options, *addr]);
+                push(&rect(src));
+                push(&point(dest));
+                OP_COPYBITS
+            }
+            Self::Pixel {
+                options,
+                point: p,
+                value,
+            } => {
+                push(&[*options]);
+                push(&point(p));
+                push(&[*value]);
+                OP_PIXEL
+            }
+            Self::Line {
+                options,
+                start,
+                end,
+            } => {
+                push(&[*options]);
+                push(&point(start));
+                push(&point(end));
+                OP_LINE
+            }
+            Self::Rectangle { options, rect: r } => {
+                push(&[*options]);
+                push(&rect(r));
+                OP_RECTANGLE
+            }
+            Self::Circle {
+                options,
+                center,
+                radius,
+            } => {
+                push(&[*options]);
+                push(&point(center));
+                push(&[radius.cast_unsigned()]);
+                OP_CIRCLE
+            }
+            Self::Number {
+                options,
+                point: p,
+                value,
+            } => {
+                push(&[*options]);
+                push(&point(p));
+                push(&[value.cast_unsigned()]);
+                OP_NUMBOX
+            }
+            Self::Unknown { opcode, args: raw } => {
+                args.extend(raw);
+                *opcode
+            }
+        };
+        Ok((opcode, args))
+    }
+}
+
+/// A parsed RIC graphics file
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Ric {
+    /// Drawing instructions, in order
+    pub ops: Vec<Op>,
+}
+
+impl Ric {
+    /// Parse the contents of a `.ric` file
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        let mut ops = Vec::new();
+        let mut rest = data;
+        while let Some((size, tail)) = rest.split_first_chunk::<2>() {
+            let size = usize::from(u16::from_le_bytes(*size));
+            let body = tail
+                .get(..size)
+                .ok_or(Error::Parse("RIC opcode truncated"))?;
+            let (opcode, args) = body
+                .split_first_chunk::<2>()
+                .ok_or(Error::Parse("RIC opcode too short"))?;
+            ops.push(Op::parse(u16::from_le_bytes(*opcode), args)?);
+            rest = &tail[size..];
+        }
+        Ok(Self { ops })
+    }
+
+    /// Serialise into the `.ric` file format
+    pub fn serialise(&self) -> Result<Vec<u8>> {
+        let mut out = Vec::new();
+        for op in &self.ops {
+            let (opcode, mut args) = op.serialise()?;
+            // keep every opcode word aligned
+            if args.len() % 2 != 0 {
+                args.push(0);
+            }
+            let size = u16::try_from(args.len() + 2)?;
+            out.extend(size.to_le_bytes());
+            out.extend(opcode.to_le_bytes());
+            out.extend(args);
+        }
+        Ok(out)
+    }
+
+    /// Build an image which draws the given bitmap, with one byte per
+    /// pixel in row-major order, with its bottom-left corner at the
+    /// drawing origin
+    pub fn from_bitmap(
+        width: usize,
+        height: usize,
+        pixels: &[u8],
+    ) -> Result<Self> {
+        let sprite = Sprite::from_bitmap(1, width, height, pixels)?;
+        let (width, height) = (width.try_into()?, height.try_into()?);
+        Ok(Self {
+            ops: vec![
+                Op::Description {
+                    options: 0,
+                    width,
+                    height,
+                },
+                Op::Sprite(sprite),
+                Op::CopyBits {
+                    options: DRAW_OPT_LOGICAL_COPY,
+                    addr: 1,
+                    src: Rect {
+                        x: 0,
+                        y: 0,
+                        width: width.cast_signed(),
+                        height: height.cast_signed(),
+                    },
+                    dest: Point::default(),
+                },
+            ],
+        })
+    }
+
+    /// Decode a PNG image into a monochrome bitmap (dark pixels set)
+    /// and build an image which draws it, as with [`Ric::from_bitmap`]
+    #[cfg(feature = "png")]
+    pub fn from_png(data: &[u8]) -> Result<Self> {
+        let (width, height, pixels) = crate::image::decode_png(data)?;
+        Self::from_bitmap(width, height, &pixels)
+    }
+
+    /// Draw the image onto a blank raster
+    #[must_use]
+    pub fn to_raster(&self) -> DisplayRaster {
+        let mut raster = [[0; DISPLAY_WIDTH]; DISPLAY_HEIGHT];
+        self.render(&mut raster);
+        raster
+    }
+
+    /// Draw the image onto the provided raster, as the firmware would
+    /// with no parameters and the origin at the bottom-left of the
+    /// screen
+    pub fn render(&self, raster: &mut DisplayRaster) {
+        let mut sprites = HashMap::new();
+        for op in &self.ops {
+            match op {
+                Op::Sprite(sprite) => {
+                    sprites.insert(sprite.addr, sprite);
+                }
+                Op::CopyBits {
+                    options,
+                    addr,
+                    src,
+                    dest,
+                } => {
+                    clear(raster, *options);
+                    if let Some(sprite) = sprites.get(addr) {
+                        copy_bits(raster, *options, sprite, *src, *dest);
+                    }
+                }
+                Op::Pixel { options, point, .. } => {
+                    clear(raster, *options);
+                    let (x, y) = to_screen(*point);
+                    raster.set_pixel((x, y), pen(*options));
+                }
+                Op::Line {
+                    options,
+                    start,
+                    end,
+                } => {
+                    clear(raster, *options);
+                    let (start, end) = (to_screen(*start), to_screen(*end));
+                    raster.line(start, end, pen(*options));
+                }
+                Op::Rectangle { options, rect } => {
+                    clear(raster, *options);
+                    let (x, width) = normalise(rect.x, rect.width);
+                    let (y, height) = normalise(rect.y, rect.height);
+                    // top-left corner in screen coordinates
+                    let top_left = to_screen(Point {
+                        x,
+                        y: y.saturating_add(height - 1),
+                    });
+                    raster.rect(
+                        top_left,
+                        width.into(),
+                        height.into(),
+                        options & DRAW_OPT_FILL_SHAPE != 0,
+                        pen(*options),
+                    );
+                }
+                Op::Circle {
+                    options,
+                    center,
+                    radius,
+                } => {
+                    clear(raster, *options);
+                    raster.circle(
+                        to_screen(*center),
+                        (*radius).into(),
+                        options & DRAW_OPT_FILL_SHAPE != 0,
+                        pen(*options),
+                    );
+                }
+                Op::Number {
+                    options,
+                    point,
+                    value,
+                } => {
+                    clear(raster, *options);
+                    // text is drawn upwards from the given point
+                    let (x, y) = to_screen(*point);
+                    // character height is 8
+                    #[allow(
+                        clippy::cast_possible_wrap,
+                        clippy::cast_possible_truncation
+                    )]
+                    let top = y - (font::CHAR_HEIGHT as i32 - 1);
+                    let text = value.to_string();
+                    raster.text((x, top), &text, pen(*options));
+                }
+                Op::Description { .. }
+                | Op::VarMap { .. }
+                | Op::Unknown { .. } => {}
+            }
+        }
+    }
+}
+
+/// Convert firmware coordinates (origin bottom-left) to screen
+/// coordinates (origin top-left)
+fn to_screen(point: Point) -> (i32, i32) {
+    // display height is 64
+    #[allow(clippy::cast_possible_wrap, clippy::cast_possible_truncation)]
+    let bottom = DISPLAY_HEIGHT as i32 - 1;
+    (point.x.into(), bottom - i32::from(point.y))
+}
+
+/// Convert a position and a possibly negative extent into a position
+/// and a positive extent
+const fn normalise(pos: i16, len: i16) -> (i16, i16) {
+    if len < 0 {
+        (
+            pos.saturating_add(len).saturating_add(1),
+            len.saturating_neg(),
+        )
+    } else {
+        (pos, len)
+    }
+}
+
+/// Colour to draw shapes in for the given options
+const fn pen(options: u16) -> u8 {
+    if options & DRAW_OPT_CLEAR_PIXELS == 0 {
+        1
+    } else {
+        0
+    }
+}
+
+/// Apply any screen clearing requested in the drawing options
+fn clear(raster: &mut DisplayRaster, options: u16) {
+    let skip = if options & DRAW_OPT_CLEAR_WHOLE_SCREEN != 0 {
+        0
+    } else if options & DRAW_OPT_CLEAR_EXCEPT_STATUS_SCREEN != 0 {
+        STATUS_HEIGHT
+    } else {
+        return;
+    };
+    for row in raster.iter_mut().skip(skip) {
+        row.fill(0);
+    }
+}
+
+/// Copy an area of a sprite onto the screen, combining pixels according
+/// to the logical operation in the drawing options
+fn copy_bits(
+    raster: &mut DisplayRaster,
+    options: u16,
+    sprite: &Sprite,
+    src: Rect,
+    dest: Point,
+) {
+    let invert = options & DRAW_OPT_CLEAR_PIXELS != 0;
+    let (left, bottom) = to_screen(dest);
+    let top = bottom - i32::from(src.height) + 1;
+    let pixels = (0..i32::from(src.height))
+        .flat_map(|row| {
+            (0..i32::from(src.width)).map(move |col| {
+                let bit = sprite
+                    .pixel(i32::from(src.x) + col, i32::from(src.y) + row);
+                u8::from(bit ^ invert)
+            })
+        })
+        .collect::<Vec<u8>>();
+    let op = match options & DRAW_OPT_LOGICAL_OPS {
+        DRAW_OPT_LOGICAL_AND => BlitOp::And,
+        DRAW_OPT_LOGICAL_OR => BlitOp::Or,
+        DRAW_OPT_LOGICAL_XOR => BlitOp::Xor,
+        _ => BlitOp::Copy,
+    };
+    // a negative width copies nothing
+    let width = usize::try_from(src.width).unwrap_or(0);
+    raster.blit((left, top), width, &pixels, op);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::system::raster_to_string;
+
+    /// A small RIC file exercising most opcodes
+    fn sample() -> Ric {
+        Ric {
+            ops: vec![
+                Op::Description {
+                    options: 0,
+                    width: 100,
+                    height: 64,
+                },
+                Op::Sprite(
+                    Sprite::from_bitmap(1, 3, 2, &[1, 0, 1, 0, 1, 0]).unwrap(),
+                ),
+                Op::CopyBits {
+                    options: 0,
+                    addr: 1,
+                    src: Rect {
+                        x: 0,
+                        y: 0,
+                        width: 3,
+                        height: 2,
+                    },
+                    dest: Point { x: 1, y: 60 },
+                },
+                Op::Pixel {
+                    options: 0,
+                    point: Point { x: 0, y: 0 },
+                    value: 0,
+                },
+                Op::Line {
+                    options: 0,
+                    start: Point { x: 0, y: 10 },
+                    end: Point { x: 4, y: 10 },
+                },
+                Op::Rectangle {
+                    options: DRAW_OPT_FILL_SHAPE,
+                    rect: Rect {
+                        x: 10,
+                        y: 10,
+                        width: 3,
+                        height: 2,
+                    },
+                },
+                Op::Circle {
+                    options: 0,
+                    center: Point { x: 50, y: 30 },
+                    radius: 2,
+                },
+                Op::Number {
+                    options: 0,
+                    point: Point { x: 20, y: 0 },
+                    value: 7,
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn roundtrip() {
+        let ric = sample();
+        let data = ric.serialise().unwrap();
+        // description opcode: size 8, opcode 0, options, width, height
+        assert_eq!(&data[..10], &[8, 0, 0, 0, 0, 0, 100, 0, 64, 0]);
+        assert_eq!(Ric::parse(&data).unwrap(), ric);
+
+        Ric::parse(&data[..data.len() - 1]).unwrap_err();
+
+        // too many entries to count in the opcode
+        let map = Ric {
+            ops: vec![Op::VarMap {
+                addr: 0,
+                map: vec![(0, 0); 0x10000],
+            }],
+        };
+        map.serialise().unwrap_err();
+    }
+
+    #[test]
+    fn render() {
+        let raster = sample().to_raster();
+        let rendered = raster_to_string(&raster);
+        let rows = rendered.lines().collect::<Vec<_>>();
+        // sprite: top row at y = 61, i.e. screen row 2
+        assert_eq!(&rows[2][..5], ".#.#.");
+        assert_eq!(&rows[3][..5], "..#..");
+        // line along y = 10
+        assert_eq!(&rows[53][..6], "#####.");
+        // filled rectangle, rows 52-53
+        assert_eq!(&rows[52][9..14], ".###.");
+        assert_eq!(&rows[53][9..14], ".###.");
+        // circle of radius 2 around (50, 33)
+        assert_eq!(&rows[31][47..54], "..###..");
+        assert_eq!(&rows[33][47..54], ".#...#.");
+        // pixel at the origin
+        assert_eq!(raster[63][0], 1);
+        // the number 7, with the bottom of the glyph on the last row
+        assert_eq!(&rows[56][20..26], "#####.");
+        assert_eq!(&rows[57][20..26], "....#.");
+    }
+
+    #[cfg(feature = "png")]
+    #[test]
+    fn png_import() {
+        let mut data = Vec::new();
+        let mut encoder = png::Encoder::new(&mut data, 3, 1);
+        encoder.set_color(png::ColorType::Grayscale);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().unwrap();
+        writer.write_image_data(&[0x00, 0xff, 0x10]).unwrap();
+        writer.finish().unwrap();
+
+        let ric = Ric::from_png(&data).unwrap();
+        assert_eq!(ric, Ric::from_bitmap(3, 1, &[1, 0, 1]).unwrap());
+    }
+}
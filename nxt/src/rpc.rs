@@ -0,0 +1,294 @@
+//! Request/response calls to a program on the brick over its mailboxes.
+//!
+//! # Protocol
+//!
+//! Requests and replies are text messages of the form `<id>:<payload>`,
+//! where `<id>` is a decimal correlation ID from 0 to 65535 chosen by
+//! the host, and `<payload>` is any text not containing a null
+//! character. The whole message must fit in
+//! [`MAX_MESSAGE_LEN`](crate::MAX_MESSAGE_LEN) bytes.
+//!
+//! 1. The host writes a request to the request inbox (0 to 9).
+//! 2. The program reads it, handles the payload, and replies with the
+//!    same ID in the response inbox (10 to 19).
+//! 3. The host reads replies from the response inbox, matching them to
+//!    requests by ID. Replies to other requests are kept for their
+//!    callers, and replies to requests which have been given up on are
+//!    dropped, so replies may be sent in any order.
+//!
+//! If no reply arrives in time, the same request is sent again with the
+//! same ID, so the program may receive it more than once. Programs
+//! whose requests aren't safe to repeat should remember the IDs they
+//! have recently handled and repeat their reply instead.
+//!
+//! In NXC, with requests in inbox 0 and replies in inbox 10 (queue 0
+//! of the `SendResponse*` functions):
+//!
+//! ```c
+//! task main() {
+//!     string msg, id, payload;
+//!     int sep;
+//!     while (true) {
+//!         if (ReceiveRemoteString(MAILBOX1, true, msg) == NO_ERR) {
+//!             sep = Pos(":", msg);
+//!             id = SubStr(msg, 0, sep);
+//!             payload = SubStr(msg, sep + 1, StrLen(msg) - sep - 1);
+//!             // handle the payload here
+//!             SendResponseString(MAILBOX1, id + ":" + payload);
+//!         }
+//!         Wait(10);
+//!     }
+//! }
+//! ```
+
+use crate::{
+    mailbox::{Message, MessageKind, RESPONSE_INBOXES},
+    DeviceError, Error, Nxt, Result, MAX_INBOX_ID,
+};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::time::{self, Instant};
+
+/// Default time to wait for each reply before resending the request
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Default number of times to resend a request before giving up
+pub const DEFAULT_RETRIES: u32 = 2;
+
+/// Interval at which the response inbox is polled while waiting for a
+/// reply
+pub const REPLY_POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// Separator between the correlation ID and the payload
+const SEPARATOR: char = ':';
+
+/// Requests waiting for a reply, shared between concurrent calls
+#[derive(Debug, Default)]
+struct Pending {
+    /// ID for the next request
+    next_id: u16,
+    /// Replies by request ID, or `None` if not received yet
+    replies: HashMap<u16, Option<String>>,
+}
+
+/// Client for calls to a program on the brick, following the protocol
+/// described in the [module docs](self)
+#[derive(Clone, Debug)]
+pub struct Rpc {
+    /// Connection to the brick
+    nxt: Nxt,
+    /// Inbox the program reads requests from
+    request_inbox: u8,
+    /// Inbox the program writes replies to
+    response_inbox: u8,
+    /// Time to wait for each reply
+    timeout: Duration,
+    /// Number of times to resend a request
+    retries: u32,
+    /// Requests waiting for a reply
+    pending: Arc<Mutex<Pending>>,
+}
+
+impl Rpc {
+    /// Create a client sending requests to `request_inbox` (0 to 9) and
+    /// reading replies from `response_inbox` (10 to 19), with the
+    /// [`DEFAULT_TIMEOUT`] and [`DEFAULT_RETRIES`]
+    pub fn new(
+        nxt: &Nxt,
+        request_inbox: u8,
+        response_inbox: u8,
+    ) -> Result<Self> {
+        for inbox in [request_inbox, response_inbox] {
+            if inbox > MAX_INBOX_ID {
+                return Err(Error::InvalidInbox(inbox));
+            }
+        }
+        if RESPONSE_INBOXES.contains(&request_inbox) {
+            return Err(Error::Serialise(
+                "Requests must be sent to an inbox from 0 to 9",
+            ));
+        }
+        if !RESPONSE_INBOXES.contains(&response_inbox) {
+            return Err(Error::Serialise(
+                "Replies must be read from a response inbox (10 to 19)",
+            ));
+        }
+        Ok(Self {
+            nxt: nxt.clone(),
+            request_inbox,
+            response_inbox,
+            timeout: DEFAULT_TIMEOUT,
+            retries: DEFAULT_RETRIES,
+            pending: Arc::default(),
+        })
+    }
+
+    /// Wait up to `timeout` for each reply
+    #[must_use]
+    pub fn with_timeout(self, timeout: Duration) -> Self {
+        Self { timeout, ..self }
+    }
+
+    /// Resend each request up to `retries` times if no reply arrives
+    #[must_use]
+    pub fn with_retries(self, retries: u32) -> Self {
+        Self { retries, ..self }
+    }
+
+    /// Send a request and wait for its reply, returning the reply's
+    /// payload. Returns [`Error::NoReply`] if there is no reply after
+    /// all the retries. Calls may be made concurrently from clones of
+    /// the client. Requires a Tokio runtime with the timer enabled.
+    pub async fn call(&self, payload: &str) -> Result<String> {
+        let id = self.register();
+        let result = self.call_with_id(id, payload).await;
+        self.pending.lock().unwrap().replies.remove(&id);
+        result
+    }
+
+    /// Send a request with the given ID until there is a reply
+    async fn call_with_id(&self, id: u16, payload: &str) -> Result<String> {
+        let request = format!("{id}{SEPARATOR}{payload}");
+        let attempts = self.retries.saturating_add(1);
+        for _ in 0..attempts {
+            self.nxt
+                .send_message(self.request_inbox, request.as_str())
+                .await?;
+            let deadline = Instant::now() + self.timeout;
+            loop {
+                self.read_replies().await?;
+                if let Some(reply) = self.take_reply(id) {
+                    return Ok(reply);
+                }
+                if Instant::now() >= deadline {
+                    break;
+                }
+                time::sleep(REPLY_POLL_INTERVAL).await;
+            }
+        }
+        Err(Error::NoReply(attempts))
+    }
+
+    /// Allocate an ID for a new request and start waiting for its reply
+    fn register(&self) -> u16 {
+        let mut pending = self.pending.lock().unwrap();
+        let mut id = pending.next_id;
+        while pending.replies.contains_key(&id) {
+            id = id.wrapping_add(1);
+        }
+        pending.next_id = id.wrapping_add(1);
+        pending.replies.insert(id, None);
+        id
+    }
+
+    /// Take the reply to a request, if it has arrived
+    fn take_reply(&self, id: u16) -> Option<String> {
+        self.pending
+            .lock()
+            .unwrap()
+            .replies
+            .get_mut(&id)
+            .and_then(Option::take)
+    }
+
+    /// Read every reply waiting in the response inbox, keeping those
+    /// which match a pending request
+    async fn read_replies(&self) -> Result<()> {
+        loop {
+            let data = match self
+                .nxt
+                .message_read(self.response_inbox, 0, true)
+                .await
+            {
+                Ok(data) => data,
+                Err(Error::Device(DeviceError::QueueEmpty)) => return Ok(()),
+                Err(e) => return Err(e),
+            };
+            // skip anything which isn't a reply in the protocol's format,
+            // including messages which aren't valid text
+            let Ok(Message::Text(text)) =
+                Message::decode(&data, MessageKind::Text)
+            else {
+                continue;
+            };
+            let Some((id, reply)) = parse_reply(&text) else {
+                continue;
+            };
+            if let Some(slot) =
+                self.pending.lock().unwrap().replies.get_mut(&id)
+            {
+                *slot = Some(reply.to_owned());
+            }
+        }
+    }
+}
+
+/// Split a reply into its ID and payload, or `None` if it isn't in the
+/// protocol's format
+fn parse_reply(text: &str) -> Option<(u16, &str)> {
+    let (id, payload) = text.split_once(SEPARATOR)?;
+    Some((id.parse().ok()?, payload))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::socket::mock::{self, Brick};
+
+    #[tokio::test]
+    async fn call() {
+        let (nxt, state) = mock::connect(Brick::default()).await;
+        let rpc = Rpc::new(&nxt, 1, 11)
+            .unwrap()
+            .with_timeout(Duration::from_millis(50))
+            .with_retries(1);
+        Rpc::new(&nxt, 10, 11).unwrap_err();
+        Rpc::new(&nxt, 1, 2).unwrap_err();
+        assert!(matches!(
+            Rpc::new(&nxt, 20, 11),
+            Err(Error::InvalidInbox(20))
+        ));
+        assert!(matches!(
+            Rpc::new(&nxt, 1, 20),
+            Err(Error::InvalidInbox(20))
+        ));
+
+        // replies to requests which aren't waiting, and messages in
+        // another format or which aren't text, are skipped
+        let brick = &state;
+        let respond = |messages: &'static [&'static [u8]]| async move {
+            time::sleep(Duration::from_millis(10)).await;
+            brick
+                .lock()
+                .unwrap()
+                .mailboxes
+                .entry(11)
+                .or_default()
+                .extend(messages.iter().map(|m| m.to_vec()));
+        };
+        let (reply, ()) = tokio::join!(
+            rpc.call("ping"),
+            respond(&[
+                b"99:old\0",
+                b"hello\0",
+                b"\xff:\xfe\0",
+                b"1:second\0",
+                b"0:pong\0",
+            ])
+        );
+        assert_eq!(reply.unwrap(), "pong");
+        assert_eq!(state.lock().unwrap().mailboxes[&1][0], b"0:ping\0");
+
+        // no reply: the request is sent again, then given up on
+        assert!(matches!(rpc.call("hi").await, Err(Error::NoReply(2))));
+        let requests = state.lock().unwrap().mailboxes[&1].clone();
+        assert_eq!(requests.len(), 3);
+        assert_eq!(requests[1], requests[2]);
+
+        assert_eq!(parse_reply("12:a:b"), Some((12, "a:b")));
+        assert_eq!(parse_reply("x:a"), None);
+    }
+}
@@ -0,0 +1,361 @@
+//! Parsing of NXT `.rxe` executable files, as described in the LEGO
+//! MINDSTORMS NXT Executable File Specification.
+//!
+//! An executable is made up of a fixed header followed by the dataspace
+//! table of contents, the default values, the clump records and the
+//! codespace. Each segment starts on an even byte boundary and all
+//! fields are little endian.
+
+use crate::{Error, Nxt, Result};
+use num_derive::FromPrimitive;
+use num_traits::FromPrimitive;
+use std::fmt::{self, Display, Formatter};
+
+/// Magic string at the start of every executable
+const FORMAT_STRING: &[u8; 14] = b"MindstormsNXT\0";
+/// Length of the file header
+const HEADER_LEN: usize = 38;
+
+/// Types of entry in the dataspace table of contents
+#[derive(Copy, Clone, Debug, PartialEq, Eq, FromPrimitive)]
+#[repr(u8)]
+#[allow(missing_docs)]
+pub enum DataType {
+    Void = 0,
+    U8 = 1,
+    S8 = 2,
+    U16 = 3,
+    S16 = 4,
+    U32 = 5,
+    S32 = 6,
+    Array = 7,
+    Cluster = 8,
+    Mutex = 9,
+    Float = 10,
+}
+
+impl TryFrom<u8> for DataType {
+    type Error = Error;
+    fn try_from(code: u8) -> Result<Self> {
+        Self::from_u8(code).ok_or(Error::Parse("Invalid DataType"))
+    }
+}
+
+/// The fixed header at the start of the executable
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Header {
+    /// File format version
+    pub version: u16,
+    /// Number of entries in the dataspace table of contents
+    pub dstoc_count: u16,
+    /// Initial size of the dataspace in RAM, in bytes
+    pub initial_size: u16,
+    /// Size of the static part of the dataspace, in bytes
+    pub static_size: u16,
+    /// Size of the default values segment, in bytes
+    pub default_data_size: u16,
+    /// Offset of the dynamic defaults within the default values
+    pub dynamic_default_offset: u16,
+    /// Size of the dynamic defaults, in bytes
+    pub dynamic_default_size: u16,
+    /// Initial head of the memory manager's dope vector list
+    pub mem_mgr_head: u16,
+    /// Initial tail of the memory manager's dope vector list
+    pub mem_mgr_tail: u16,
+    /// Offset of the dope vector array within the dataspace
+    pub dope_vector_offset: u16,
+    /// Number of clumps (tasks and subroutines)
+    pub clump_count: u16,
+    /// Number of 16-bit words of code
+    pub code_word_count: u16,
+}
+
+/// An entry in the dataspace table of contents
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct DstocEntry {
+    /// Type of the entry
+    pub data_type: DataType,
+    /// Entry flags
+    pub flags: u8,
+    /// Type-specific description, e.g. the offset of a scalar within
+    /// the dataspace
+    pub desc: u16,
+}
+
+/// A clump record, describing a task or subroutine
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Clump {
+    /// Number of dependencies which must finish before this clump runs
+    pub fire_count: u8,
+    /// Offset of the first instruction within the codespace, in words
+    pub code_start: u16,
+    /// Clumps which depend on this one
+    pub dependents: Vec<u8>,
+}
+
+/// A parsed executable
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Rxe {
+    /// File header
+    pub header: Header,
+    /// Dataspace table of contents
+    pub dstoc: Vec<DstocEntry>,
+    /// Default values for the static dataspace
+    pub static_defaults: Vec<u8>,
+    /// Default values for dynamic (array) data
+    pub dynamic_defaults: Vec<u8>,
+    /// Clump records
+    pub clumps: Vec<Clump>,
+    /// Codespace
+    pub code: Vec<u16>,
+}
+
+/// Cursor over the file data which keeps track of segment alignment
+struct Reader<'a> {
+    /// Complete file data
+    data: &'a [u8],
+    /// Current offset into the data
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    /// Read a slice of the given length
+    fn slice(&mut self, len: usize) -> Result<&'a [u8]> {
+        let out = self
+            .data
+            .get(self.pos..self.pos + len)
+            .ok_or(Error::Parse("RXE file truncated"))?;
+        self.pos += len;
+        Ok(out)
+    }
+
+    /// Read a single byte
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.slice(1)?[0])
+    }
+
+    /// Read a little-endian word
+    fn u16(&mut self) -> Result<u16> {
+        let bytes = self.slice(2)?;
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    /// Skip to the next even offset
+    const fn align(&mut self) {
+        self.pos += self.pos % 2;
+    }
+}
+
+impl Rxe {
+    /// Parse and validate the contents of an `.rxe` file
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        if data.len() < HEADER_LEN {
+            return Err(Error::Parse("RXE header too short"));
+        }
+        if &data[..FORMAT_STRING.len()] != FORMAT_STRING {
+            return Err(Error::Parse("Not an RXE file"));
+        }
+
+        let mut rd = Reader {
+            data,
+            pos: FORMAT_STRING.len(),
+        };
+        // the version is the only big endian field
+        let version = u16::from_be_bytes([rd.u8()?, rd.u8()?]);
+        let header = Header {
+            version,
+            dstoc_count: rd.u16()?,
+            initial_size: rd.u16()?,
+            static_size: rd.u16()?,
+            default_data_size: rd.u16()?,
+            dynamic_default_offset: rd.u16()?,
+            dynamic_default_size: rd.u16()?,
+            mem_mgr_head: rd.u16()?,
+            mem_mgr_tail: rd.u16()?,
+            dope_vector_offset: rd.u16()?,
+            clump_count: rd.u16()?,
+            code_word_count: rd.u16()?,
+        };
+
+        let dstoc = (0..header.dstoc_count)
+            .map(|_| {
+                Ok(DstocEntry {
+                    data_type: rd.u8()?.try_into()?,
+                    flags: rd.u8()?,
+                    desc: rd.u16()?,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let dynamic_offset = usize::from(header.dynamic_default_offset);
+        let dynamic_size = usize::from(header.dynamic_default_size);
+        if dynamic_offset + dynamic_size != header.default_data_size.into() {
+            return Err(Error::Parse("RXE default data sizes inconsistent"));
+        }
+        let static_defaults = rd.slice(dynamic_offset)?.to_vec();
+        let dynamic_defaults = rd.slice(dynamic_size)?.to_vec();
+        rd.align();
+
+        let records = (0..header.clump_count)
+            .map(|_| Ok((rd.u8()?, rd.u8()?, rd.u16()?)))
+            .collect::<Result<Vec<_>>>()?;
+        let clumps = records
+            .into_iter()
+            .map(|(fire_count, dependent_count, code_start)| {
+                let dependents = rd.slice(dependent_count.into())?.to_vec();
+                Ok(Clump {
+                    fire_count,
+                    code_start,
+                    dependents,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        rd.align();
+
+        let code = (0..header.code_word_count)
+            .map(|_| rd.u16())
+            .collect::<Result<Vec<_>>>()?;
+
+        let rxe = Self {
+            header,
+            dstoc,
+            static_defaults,
+            dynamic_defaults,
+            clumps,
+            code,
+        };
+        rxe.validate()?;
+        Ok(rxe)
+    }
+
+    /// Check that the clump records refer to valid code and clumps
+    fn validate(&self) -> Result<()> {
+        for clump in &self.clumps {
+            if clump.code_start >= self.header.code_word_count {
+                return Err(Error::Parse("RXE clump starts outside code"));
+            }
+            if clump
+                .dependents
+                .iter()
+                .any(|&dep| u16::from(dep) >= self.header.clump_count)
+            {
+                return Err(Error::Parse("RXE clump has invalid dependent"));
+            }
+        }
+        Ok(())
+    }
+
+    /// Size of the codespace, in bytes
+    #[must_use]
+    pub const fn code_size(&self) -> usize {
+        self.code.len() * 2
+    }
+}
+
+impl Display for Rxe {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        let header = &self.header;
+        writeln!(fmt, "Format version:   {}", header.version)?;
+        writeln!(fmt, "Dataspace:        {} entries", header.dstoc_count)?;
+        writeln!(fmt, "Initial memory:   {} bytes", header.initial_size)?;
+        writeln!(fmt, "Static data:      {} bytes", header.static_size)?;
+        writeln!(
+            fmt,
+            "Default data:     {} bytes ({} dynamic)",
+            header.default_data_size, header.dynamic_default_size
+        )?;
+        writeln!(fmt, "Clumps:           {}", header.clump_count)?;
+        writeln!(fmt, "Code:             {} bytes", self.code_size())
+    }
+}
+
+impl Nxt {
+    /// Download and parse the named executable from the brick
+    pub async fn inspect_program(&self, name: &str) -> Result<Rxe> {
+        let data = self.download_file(name).await?;
+        Rxe::parse(&data)
+    }
+
+    /// Check that the data is a well-formed executable before uploading
+    /// it to the brick under the given name
+    pub async fn upload_program(&self, name: &str, data: &[u8]) -> Result<Rxe> {
+        let rxe = Rxe::parse(data)?;
+        self.upload_file(name, data).await?;
+        Ok(rxe)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// A minimal executable with two clumps and three dataspace entries
+    const RXE: &[u8] = &[
+        // format string and version
+        b'M', b'i', b'n', b'd', b's', b't', b'o', b'r', b'm', b's', b'N', b'X',
+        b'T', 0, 0, 5, // dstoc count, initial size, static size
+        3, 0, 12, 0, 8, 0, // default data size, dynamic offset and size
+        5, 0, 3, 0, 2, 0,
+        // mem manager head and tail, dope vector offset
+        0xff, 0xff, 0xff, 0xff, 8, 0, // clump count, code word count
+        2, 0, 3, 0, // dstoc
+        1, 0, 0, 0, //
+        4, 1, 2, 0, //
+        7, 0, 4, 0, //
+        // static defaults, dynamic defaults, padding
+        1, 2, 3, 4, 5, 0, // clump records, dependents, padding
+        0, 1, 0, 0, //
+        1, 0, 2, 0, //
+        1, 0, // code
+        0x01, 0x10, 0x02, 0x20, 0x03, 0x30,
+    ];
+
+    #[test]
+    fn parse() {
+        let rxe = Rxe::parse(RXE).unwrap();
+        assert_eq!(rxe.header.version, 5);
+        assert_eq!(rxe.header.initial_size, 12);
+        assert_eq!(
+            rxe.dstoc[1],
+            DstocEntry {
+                data_type: DataType::S16,
+                flags: 1,
+                desc: 2,
+            }
+        );
+        assert_eq!(rxe.static_defaults, [1, 2, 3]);
+        assert_eq!(rxe.dynamic_defaults, [4, 5]);
+        assert_eq!(
+            rxe.clumps,
+            [
+                Clump {
+                    fire_count: 0,
+                    code_start: 0,
+                    dependents: vec![1],
+                },
+                Clump {
+                    fire_count: 1,
+                    code_start: 2,
+                    dependents: vec![],
+                },
+            ]
+        );
+        assert_eq!(rxe.code, [0x1001, 0x2002, 0x3003]);
+
+        let summary = rxe.to_string();
+        assert!(summary.contains("Clumps:           2\n"));
+        assert!(summary.contains("Code:             6 bytes\n"));
+    }
+
+    #[test]
+    fn invalid() {
+        Rxe::parse(&RXE[..RXE.len() - 1]).unwrap_err();
+        let mut bad_magic = RXE.to_vec();
+        bad_magic[0] = b'm';
+        Rxe::parse(&bad_magic).unwrap_err();
+        // second clump starts beyond the end of the code
+        let mut bad_clump = RXE.to_vec();
+        bad_clump[62] = 3;
+        Rxe::parse(&bad_clump).unwrap_err();
+    }
+}
@@ -0,0 +1,369 @@
+//! Client for the SAM-BA boot assistant, used to flash new firmware
+//! after [`Nxt::boot`](crate::Nxt::boot) has put the brick into
+//! firmware update mode.
+//!
+//! In boot mode the brick enumerates as a USB CDC serial device (e.g.
+//! `/dev/ttyACM0`) speaking the SAM-BA monitor protocol. Any transport
+//! implementing [`Read`] and [`Write`] can be used, such as a serial
+//! port opened in raw mode.
+
+use crate::{
+    firmware::{
+        Firmware, FLASH_BASE, FLASH_LOCK_REGION_PAGES, FLASH_PAGE_SIZE,
+        FLASH_SIZE,
+    },
+    Error, Result,
+};
+use std::io::{Read, Write};
+
+/// Memory controller flash mode register
+const MC_FMR: u32 = 0xffff_ff60;
+/// Memory controller flash command register
+const MC_FCR: u32 = 0xffff_ff64;
+/// Memory controller flash status register
+const MC_FSR: u32 = 0xffff_ff68;
+/// Flash mode: 0x34 cycles per microsecond at 48 MHz, one wait state
+const FMR_VALUE: u32 = 0x0034_0100;
+/// Key which must accompany every flash command
+const FCR_KEY: u32 = 0x5a00_0000;
+/// Flash command: write page
+const FCMD_WRITE_PAGE: u32 = 0x01;
+/// Flash command: clear lock bit
+const FCMD_CLEAR_LOCK: u32 = 0x04;
+/// Flash status: ready for a new command
+const FSR_READY: u32 = 0x01;
+/// Flash status: attempted to write to a locked region
+const FSR_LOCK_ERROR: u32 = 0x04;
+/// Flash status: invalid command
+const FSR_PROG_ERROR: u32 = 0x08;
+/// Number of status polls before giving up on the flash controller
+const READY_POLLS: usize = 1000;
+
+/// A connection to the SAM-BA monitor
+#[derive(Debug)]
+pub struct Samba<T> {
+    /// Underlying serial transport
+    port: T,
+}
+
+impl<T: Read + Write> Samba<T> {
+    /// Connect to the monitor and switch it to binary mode
+    pub fn new(port: T) -> Result<Self> {
+        let mut samba = Self { port };
+        samba.command("N#")?;
+        samba.expect_prompt()?;
+        Ok(samba)
+    }
+
+    /// Release the underlying transport
+    pub fn into_inner(self) -> T {
+        self.port
+    }
+
+    /// Send a single command to the monitor
+    fn command(&mut self, cmd: &str) -> Result<()> {
+        self.port.write_all(cmd.as_bytes())?;
+        self.port.flush()?;
+        Ok(())
+    }
+
+    /// Read the `\n\r` sequence which terminates textual replies
+    fn expect_prompt(&mut self) -> Result<()> {
+        let mut buf = [0; 2];
+        self.port.read_exact(&mut buf)?;
+        if buf == *b"\n\r" {
+            Ok(())
+        } else {
+            Err(Error::Parse("Unexpected reply from SAM-BA"))
+        }
+    }
+
+    /// Read the monitor's version string
+    pub fn version(&mut self) -> Result<String> {
+        self.command("V#")?;
+        let mut out = Vec::new();
+        let mut byte = [0];
+        while !out.ends_with(b"\n\r") {
+            self.port.read_exact(&mut byte)?;
+            out.push(byte[0]);
+        }
+        out.truncate(out.len() - 2);
+        Ok(String::from_utf8(out)?)
+    }
+
+    /// Write a 32-bit word to the given address
+    pub fn write_word(&mut self, addr: u32, value: u32) -> Result<()> {
+        self.command(&format!("W{addr:08X},{value:08X}#"))
+    }
+
+    /// Read a 32-bit word from the given address
+    pub fn read_word(&mut self, addr: u32) -> Result<u32> {
+        self.command(&format!("w{addr:08X},4#"))?;
+        let mut buf = [0; 4];
+        self.port.read_exact(&mut buf)?;
+        Ok(u32::from_le_bytes(buf))
+    }
+
+    /// Read a block of memory starting at the given address
+    pub fn read(&mut self, addr: u32, len: usize) -> Result<Vec<u8>> {
+        self.command(&format!("R{addr:08X},{len:08X}#"))?;
+        let mut buf = vec![0; len];
+        self.port.read_exact(&mut buf)?;
+        Ok(buf)
+    }
+
+    /// Start executing code at the given address. The monitor does not
+    /// reply, and the connection is lost when jumping into firmware.
+    pub fn go(&mut self, addr: u32) -> Result<()> {
+        self.command(&format!("G{addr:08X}#"))
+    }
+
+    /// Issue a command to the flash controller and wait for it to finish
+    fn flash_command(&mut self, page: usize, cmd: u32) -> Result<()> {
+        let page = u32::try_from(page)?;
+        self.write_word(MC_FCR, FCR_KEY | page << 8 | cmd)?;
+        for _ in 0..READY_POLLS {
+            let status = self.read_word(MC_FSR)?;
+            if status & FSR_LOCK_ERROR != 0 {
+                return Err(Error::Flash("Write to locked flash region"));
+            }
+            if status & FSR_PROG_ERROR != 0 {
+                return Err(Error::Flash("Invalid flash command"));
+            }
+            if status & FSR_READY != 0 {
+                return Ok(());
+            }
+        }
+        Err(Error::Flash("Timed out waiting for flash controller"))
+    }
+
+    /// Clear the lock bits protecting every region of flash
+    pub fn unlock_all(&mut self) -> Result<()> {
+        self.write_word(MC_FMR, FMR_VALUE)?;
+        let pages = FLASH_SIZE / FLASH_PAGE_SIZE;
+        for page in (0..pages).step_by(FLASH_LOCK_REGION_PAGES) {
+            self.flash_command(page, FCMD_CLEAR_LOCK)?;
+        }
+        Ok(())
+    }
+
+    /// Program a single page of flash. The page's region must have been
+    /// unlocked first.
+    pub fn write_page(
+        &mut self,
+        page: usize,
+        data: &[u8; FLASH_PAGE_SIZE],
+    ) -> Result<()> {
+        let base = page_addr(page)?;
+        // fill the controller's page latch one word at a time
+        for (addr, word) in (base..).step_by(4).zip(data.chunks_exact(4)) {
+            let value = u32::from_le_bytes(word.try_into().unwrap());
+            self.write_word(addr, value)?;
+        }
+        self.flash_command(page, FCMD_WRITE_PAGE)
+    }
+
+    /// Read back the flash and check that it matches the image
+    pub fn verify(&mut self, firmware: &Firmware) -> Result<()> {
+        for (page, expected) in firmware.pages().enumerate() {
+            let actual = self.read(page_addr(page)?, FLASH_PAGE_SIZE)?;
+            if let Some(idx) =
+                expected.iter().zip(&actual).position(|(a, b)| a != b)
+            {
+                return Err(Error::VerifyMismatch {
+                    chunk: page,
+                    offset: page * FLASH_PAGE_SIZE + idx,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Unlock the flash, write the firmware image, verify it and then
+    /// boot into the new firmware
+    pub fn flash(&mut self, firmware: &Firmware) -> Result<()> {
+        self.unlock_all()?;
+        for (page, data) in firmware.pages().enumerate() {
+            self.write_page(page, &data)?;
+        }
+        self.verify(firmware)?;
+        self.go(FLASH_BASE)
+    }
+}
+
+/// Address of the given flash page
+fn page_addr(page: usize) -> Result<u32> {
+    if page >= FLASH_SIZE / FLASH_PAGE_SIZE {
+        return Err(Error::Serialise("Flash page out of range"));
+    }
+    Ok(FLASH_BASE + u32::try_from(page * FLASH_PAGE_SIZE)?)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io;
+
+    /// In-process stand-in for the SAM-BA monitor and the flash
+    /// controller of an AT91SAM7S256
+    struct FakeSamba {
+        /// Partially received command
+        input: Vec<u8>,
+        /// Pending reply bytes
+        output: Vec<u8>,
+        /// Contents of flash
+        flash: Vec<u8>,
+        /// Flash controller page latch
+        latch: [u8; FLASH_PAGE_SIZE],
+        /// One lock bit per region, all set initially
+        locks: u16,
+        /// Current flash status register
+        status: u32,
+        /// Address passed to the last `G` command
+        started: Option<u32>,
+    }
+
+    impl FakeSamba {
+        fn new() -> Self {
+            Self {
+                input: Vec::new(),
+                output: Vec::new(),
+                flash: vec![0xff; FLASH_SIZE],
+                latch: [0xff; FLASH_PAGE_SIZE],
+                locks: 0xffff,
+                status: FSR_READY,
+                started: None,
+            }
+        }
+
+        /// Handle a write to the flash command register
+        fn flash_command(&mut self, value: u32) {
+            let page = usize::try_from(value >> 8 & 0x3ff).unwrap();
+            let region = page / FLASH_LOCK_REGION_PAGES;
+            self.status = FSR_READY;
+            if value & 0xff00_0000 != FCR_KEY {
+                self.status |= FSR_PROG_ERROR;
+            } else if value & 0xff == FCMD_CLEAR_LOCK {
+                self.locks &= !(1 << region);
+            } else if value & 0xff == FCMD_WRITE_PAGE {
+                if self.locks & (1 << region) == 0 {
+                    let start = page * FLASH_PAGE_SIZE;
+                    self.flash[start..start + FLASH_PAGE_SIZE]
+                        .copy_from_slice(&self.latch);
+                } else {
+                    self.status |= FSR_LOCK_ERROR;
+                }
+            } else {
+                self.status |= FSR_PROG_ERROR;
+            }
+        }
+
+        /// Execute a complete command (without the trailing `#`)
+        fn execute(&mut self, cmd: &str) {
+            let hex = |s: &str| u32::from_str_radix(s, 16).unwrap();
+            let (op, args) = cmd.split_at(1);
+            let mut args = args.split(',').map(hex);
+            let mut arg = || args.next().unwrap();
+            let flash_base = usize::try_from(FLASH_BASE).unwrap();
+            match op {
+                "N" => self.output.extend(b"\n\r"),
+                "V" => self.output.extend(b"v1.4 Nov 10 2004\n\r"),
+                "W" => match (arg(), arg()) {
+                    (MC_FCR, value) => self.flash_command(value),
+                    (MC_FMR, _) => {}
+                    (addr, value) => {
+                        let offset =
+                            usize::try_from(addr).unwrap() % FLASH_PAGE_SIZE;
+                        self.latch[offset..offset + 4]
+                            .copy_from_slice(&value.to_le_bytes());
+                    }
+                },
+                "w" => {
+                    assert_eq!(arg(), MC_FSR);
+                    self.output.extend(self.status.to_le_bytes());
+                }
+                "R" => {
+                    let addr = usize::try_from(arg()).unwrap() - flash_base;
+                    let len = usize::try_from(arg()).unwrap();
+                    self.output.extend(&self.flash[addr..addr + len]);
+                }
+                "G" => self.started = Some(arg()),
+                _ => panic!("unknown command {cmd}"),
+            }
+        }
+    }
+
+    impl Write for FakeSamba {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            for &byte in buf {
+                if byte == b'#' {
+                    let cmd =
+                        String::from_utf8(std::mem::take(&mut self.input))
+                            .unwrap();
+                    self.execute(&cmd);
+                } else {
+                    self.input.push(byte);
+                }
+            }
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Read for FakeSamba {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let len = buf.len().min(self.output.len());
+            buf[..len].copy_from_slice(&self.output[..len]);
+            self.output.drain(..len);
+            Ok(len)
+        }
+    }
+
+    /// A firmware image spanning several lock regions
+    fn firmware() -> Firmware {
+        let mut data = 0xea00_0006u32.to_le_bytes().to_vec();
+        data.extend((0..=255).cycle().take(20_000));
+        Firmware::parse(&data).unwrap()
+    }
+
+    #[test]
+    fn flash_firmware() {
+        let mut fake = FakeSamba::new();
+        let firmware = firmware();
+        let mut samba = Samba::new(&mut fake).unwrap();
+        assert_eq!(samba.version().unwrap(), "v1.4 Nov 10 2004");
+        samba.flash(&firmware).unwrap();
+
+        assert_eq!(fake.locks, 0);
+        assert_eq!(&fake.flash[..firmware.data.len()], firmware.data);
+        assert!(fake.flash[firmware.data.len()..].iter().all(|&b| b == 0xff));
+        assert_eq!(fake.started, Some(FLASH_BASE));
+    }
+
+    #[test]
+    fn locked_and_corrupt() {
+        let mut fake = FakeSamba::new();
+        let firmware = firmware();
+        let mut samba = Samba::new(&mut fake).unwrap();
+        let page = firmware.pages().next().unwrap();
+        assert!(matches!(samba.write_page(0, &page), Err(Error::Flash(_))));
+        samba.unlock_all().unwrap();
+        for (idx, page) in firmware.pages().enumerate() {
+            samba.write_page(idx, &page).unwrap();
+        }
+        samba.verify(&firmware).unwrap();
+
+        fake.flash[FLASH_PAGE_SIZE * 3 + 5] ^= 0x10;
+        let mut samba = Samba::new(&mut fake).unwrap();
+        assert!(matches!(
+            samba.verify(&firmware),
+            Err(Error::VerifyMismatch {
+                chunk: 3,
+                offset: 773
+            })
+        ));
+    }
+}
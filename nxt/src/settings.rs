@@ -0,0 +1,180 @@
+//! Brick settings and power control through the UI and IO control
+//! module iomaps: speaker volume, sleep timeout, battery status and
+//! switching off or rebooting into firmware update mode.
+
+use crate::{
+    iomap::{ioctrl, sound, ui},
+    Error, Nxt, Result,
+};
+use std::time::Duration;
+
+/// Highest speaker volume setting
+pub const MAX_VOLUME: u8 = 4;
+
+/// Battery status, as shown in the status bar
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct BatteryStatus {
+    /// Battery voltage, in mV
+    pub voltage: u16,
+    /// Charge level shown in the status bar, 0 to 4
+    pub level: u8,
+    /// Whether the rechargeable battery pack is fitted rather than AA
+    /// cells
+    pub rechargeable: bool,
+}
+
+impl Nxt {
+    /// Read the speaker volume setting, 0 to [`MAX_VOLUME`]
+    pub async fn get_volume(&self) -> Result<u8> {
+        self.read_field(ui::VOLUME).await
+    }
+
+    /// Change the speaker volume setting, 0 to [`MAX_VOLUME`]. The new
+    /// volume applies to sounds played from then on, and is shown in
+    /// the on-brick settings menu.
+    pub async fn set_volume(&self, volume: u8) -> Result<()> {
+        if volume > MAX_VOLUME {
+            return Err(Error::Serialise("Volume must be between 0 and 4"));
+        }
+        self.write_field(ui::VOLUME, &volume).await?;
+        self.write_field(sound::VOLUME, &volume).await
+    }
+
+    /// Read how long the brick waits without activity before switching
+    /// itself off, or `None` if it never does
+    pub async fn get_sleep_timeout(&self) -> Result<Option<Duration>> {
+        let minutes = self.read_field(ui::SLEEP_TIMEOUT).await?;
+        Ok(
+            (minutes != 0)
+                .then(|| Duration::from_secs(u64::from(minutes) * 60)),
+        )
+    }
+
+    /// Change how long the brick waits without activity before switching
+    /// itself off, or stop it switching off with `None`. The timeout is
+    /// stored in whole minutes, up to 255. The sleep timer is restarted.
+    pub async fn set_sleep_timeout(
+        &self,
+        timeout: Option<Duration>,
+    ) -> Result<()> {
+        let minutes = match timeout {
+            None => 0,
+            Some(timeout) => {
+                let secs = timeout.as_secs();
+                if secs == 0 || secs % 60 != 0 || timeout.subsec_nanos() != 0 {
+                    return Err(Error::Serialise(
+                        "Sleep timeout must be a whole number of minutes",
+                    ));
+                }
+                u8::try_from(secs / 60)?
+            }
+        };
+        self.write_field(ui::SLEEP_TIMEOUT, &minutes).await?;
+        let flags = self.read_field(ui::FLAGS).await?;
+        self.write_field(ui::FLAGS, &(flags | ui::FLAGS_RESET_SLEEP_TIMER))
+            .await
+    }
+
+    /// Read the battery voltage, charge level and type
+    pub async fn get_battery_status(&self) -> Result<BatteryStatus> {
+        Ok(BatteryStatus {
+            voltage: self.read_field(ui::BATTERY_VOLTAGE).await?,
+            level: self.read_field(ui::BATTERY_STATE).await?,
+            rechargeable: self.read_field(ui::RECHARGEABLE).await?,
+        })
+    }
+
+    /// Switch the brick off. The connection is lost once the brick acts
+    /// on the request.
+    pub async fn power_off(&self) -> Result<()> {
+        self.write_field(ioctrl::POWER_ON, &ioctrl::POWER_DOWN)
+            .await
+    }
+
+    /// Reboot into firmware update mode, as [`Nxt::boot`] does, but
+    /// through the IO control module so it also works over Bluetooth.
+    /// Warning, this is not recoverable without loading new firmware,
+    /// which can be done with [`samba::Samba`](crate::samba::Samba).
+    pub async fn reboot_to_firmware_update(&self, sure: bool) -> Result<()> {
+        if !sure {
+            return Err(Error::Serialise(
+                "Are you sure? This is not recoverable",
+            ));
+        }
+        self.write_field(ioctrl::POWER_ON, &ioctrl::POWER_BOOT)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::socket::mock::{self, Brick};
+
+    /// A duration of whole minutes
+    const fn minutes(minutes: u64) -> Duration {
+        Duration::from_secs(minutes * 60)
+    }
+
+    #[tokio::test]
+    async fn settings() {
+        let mut brick = Brick::default();
+        brick.add_iomap(ui::MODULE, 64);
+        brick.add_iomap(sound::MODULE, 32);
+        brick.add_iomap(ioctrl::MODULE, 2);
+        let (nxt, state) = mock::connect(brick).await;
+        let read = |field: (u32, usize)| {
+            state.lock().unwrap().iomaps[&field.0][field.1]
+        };
+        let ui_byte = |offset: u16| (ui::MODULE.id, usize::from(offset));
+
+        nxt.set_volume(2).await.unwrap();
+        assert_eq!(nxt.get_volume().await.unwrap(), 2);
+        assert_eq!(read((sound::MODULE.id, 29)), 2);
+        nxt.set_volume(MAX_VOLUME + 1).await.unwrap_err();
+
+        assert_eq!(nxt.get_sleep_timeout().await.unwrap(), None);
+        nxt.set_sleep_timeout(Some(minutes(10))).await.unwrap();
+        assert_eq!(read(ui_byte(ui::SLEEP_TIMEOUT.offset)), 10);
+        assert_eq!(
+            read(ui_byte(ui::FLAGS.offset)),
+            ui::FLAGS_RESET_SLEEP_TIMER
+        );
+        assert_eq!(nxt.get_sleep_timeout().await.unwrap(), Some(minutes(10)));
+        nxt.set_sleep_timeout(Some(Duration::from_secs(90)))
+            .await
+            .unwrap_err();
+        nxt.set_sleep_timeout(Some(minutes(256))).await.unwrap_err();
+        nxt.set_sleep_timeout(None).await.unwrap();
+        assert_eq!(nxt.get_sleep_timeout().await.unwrap(), None);
+
+        let set_ui = |offset: usize, data: &[u8]| {
+            state
+                .lock()
+                .unwrap()
+                .iomaps
+                .get_mut(&ui::MODULE.id)
+                .unwrap()[offset..offset + data.len()]
+                .copy_from_slice(data);
+        };
+        set_ui(4, &7800u16.to_le_bytes());
+        set_ui(30, &[3]);
+        set_ui(35, &[1]);
+        assert_eq!(
+            nxt.get_battery_status().await.unwrap(),
+            BatteryStatus {
+                voltage: 7800,
+                level: 3,
+                rechargeable: true,
+            }
+        );
+
+        nxt.reboot_to_firmware_update(false).await.unwrap_err();
+        assert_eq!(read((ioctrl::MODULE.id, 0)), 0);
+        nxt.power_off().await.unwrap();
+        assert_eq!(
+            state.lock().unwrap().iomaps[&ioctrl::MODULE.id],
+            ioctrl::POWER_DOWN.to_le_bytes()
+        );
+    }
+}
@@ -9,6 +9,9 @@ pub mod usb;
 #[cfg(feature = "bluetooth")]
 pub mod bluetooth;
 
+#[cfg(test)]
+pub mod mock;
+
 /// Abstraction over various socket types (namely USB and Bluetooth) to
 /// allow the base NXT struct to transparently use any supported backend
 #[async_trait::async_trait]
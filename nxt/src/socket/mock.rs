@@ -0,0 +1,553 @@
+//! In-process stand-in for an NXT brick, used to exercise the `Nxt`
+//! APIs in unit tests without any hardware attached
+
+use super::Socket;
+use crate::{
+    iomap::{
+        command::{self, ProgStatus},
+        Module,
+    },
+    protocol::{DeviceError, Opcode, Packet},
+    system::WriteMode,
+    Result,
+};
+use std::{
+    collections::{BTreeMap, VecDeque},
+    sync::{Arc, Mutex},
+};
+
+/// Length of the filename field, including null terminator
+const FILENAME_LEN: usize = 20;
+
+/// Reply payload (after the status byte), or the error status to send
+type Reply = std::result::Result<Vec<u8>, DeviceError>;
+
+/// A file stored on the simulated brick
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MockFile {
+    /// File contents
+    pub data: Vec<u8>,
+    /// Mode the file was created with
+    pub mode: WriteMode,
+}
+
+/// State of an open handle on the simulated brick
+#[derive(Debug)]
+enum Handle {
+    /// File opened for reading
+    Read {
+        /// Name of the file
+        name: String,
+        /// Current read offset
+        pos: usize,
+    },
+    /// File opened for writing; committed to storage on close
+    Write {
+        /// Name of the file
+        name: String,
+        /// Data written so far
+        data: Vec<u8>,
+        /// Mode the file was opened with
+        mode: WriteMode,
+    },
+    /// File search in progress
+    Find {
+        /// Remaining matches
+        matches: VecDeque<String>,
+    },
+    /// Module search in progress
+    FindModule {
+        /// Remaining matches
+        matches: VecDeque<String>,
+    },
+}
+
+/// Simulated brick state. Tests may inspect and modify this directly
+/// via the shared handle.
+#[derive(Debug)]
+pub struct Brick {
+    /// Brick name
+    pub name: String,
+    /// Firmware version as `(major, minor)`
+    pub fw: (u8, u8),
+    /// Protocol version as `(major, minor)`
+    pub prot: (u8, u8),
+    /// Free user flash, in bytes
+    pub flash: u32,
+    /// Stored files
+    pub files: BTreeMap<String, MockFile>,
+    /// Number of subsequent file writes which will have their first
+    /// byte corrupted, to simulate a bad link
+    pub corrupt_writes: u32,
+    /// Module iomaps, by module ID
+    pub iomaps: BTreeMap<u32, Vec<u8>>,
+    /// Module IDs, by name
+    pub modules: BTreeMap<String, u32>,
+    /// Contents of the USB and high speed poll buffers, indexed by
+    /// `BufType`
+    pub poll_bufs: [VecDeque<u8>; 2],
+    /// Queued messages, by inbox, including their null terminators
+    pub mailboxes: BTreeMap<u8, VecDeque<Vec<u8>>>,
+    /// Interpreter state shown as soon as a program is started, to
+    /// simulate one which exits before it can be polled; `None` leaves
+    /// it running
+    pub program_exit: Option<ProgStatus>,
+    /// Open handles
+    handles: BTreeMap<u8, Handle>,
+}
+
+impl Default for Brick {
+    fn default() -> Self {
+        Self {
+            name: "mock".into(),
+            fw: (1, 31),
+            prot: (1, 124),
+            flash: 64 * 1024,
+            files: BTreeMap::new(),
+            corrupt_writes: 0,
+            iomaps: BTreeMap::new(),
+            modules: BTreeMap::new(),
+            poll_bufs: Default::default(),
+            mailboxes: BTreeMap::new(),
+            program_exit: None,
+            handles: BTreeMap::new(),
+        }
+    }
+}
+
+impl Brick {
+    /// Add a file to the simulated storage
+    pub fn add_file(&mut self, name: &str, data: &[u8], mode: WriteMode) {
+        self.files.insert(
+            name.into(),
+            MockFile {
+                data: data.to_vec(),
+                mode,
+            },
+        );
+    }
+
+    /// Add a module with a zero-filled iomap of the given length
+    pub fn add_iomap(&mut self, module: Module, len: usize) {
+        self.modules.insert(module.name.into(), module.id);
+        self.iomaps.insert(module.id, vec![0; len]);
+    }
+
+    /// Number of handles currently open
+    pub fn handles_open(&self) -> usize {
+        self.handles.len()
+    }
+
+    /// Allocate the lowest free handle number
+    fn alloc_handle(&mut self, handle: Handle) -> u8 {
+        let id = (0..=u8::MAX)
+            .find(|id| !self.handles.contains_key(id))
+            .unwrap();
+        self.handles.insert(id, handle);
+        id
+    }
+
+    /// Handle a request packet and return the reply payload (after the
+    /// status byte)
+    fn handle(&mut self, req: &mut Packet) -> Reply {
+        let mut out = Vec::new();
+        match req.opcode {
+            Opcode::SystemVersions => {
+                out.extend([self.prot.1, self.prot.0, self.fw.1, self.fw.0]);
+            }
+            Opcode::SystemDeviceinfo => {
+                let mut name = self.name.as_bytes().to_vec();
+                name.resize(15, 0);
+                out.extend(name);
+                out.extend([0; 6]);
+                out.push(0);
+                out.extend([0; 4]);
+                out.extend(self.flash.to_le_bytes());
+            }
+            _ => return self.handle_file(req),
+        }
+        Ok(out)
+    }
+
+    /// Handle a file search or read request
+    fn handle_file(&mut self, req: &mut Packet) -> Reply {
+        let mut out = Vec::new();
+        match req.opcode {
+            Opcode::SystemFindfirst => {
+                let pattern = req.read_filename().unwrap();
+                let mut matches = self
+                    .files
+                    .keys()
+                    .filter(|name| matches_pattern(&pattern, name))
+                    .cloned()
+                    .collect::<VecDeque<_>>();
+                let name =
+                    matches.pop_front().ok_or(DeviceError::FileNotFound)?;
+                let id = self.alloc_handle(Handle::Find { matches });
+                out.push(id);
+                self.push_find_result(&mut out, &name);
+            }
+            Opcode::SystemFindnext => {
+                let id = req.read_u8().unwrap();
+                let Some(Handle::Find { matches }) = self.handles.get_mut(&id)
+                else {
+                    return Err(DeviceError::IllegalHandle);
+                };
+                let Some(name) = matches.pop_front() else {
+                    self.handles.remove(&id);
+                    return Err(DeviceError::FileNotFound);
+                };
+                out.push(id);
+                self.push_find_result(&mut out, &name);
+            }
+            Opcode::SystemOpenread => {
+                let name = req.read_filename().unwrap();
+                let len = self
+                    .files
+                    .get(&name)
+                    .ok_or(DeviceError::FileNotFound)?
+                    .data
+                    .len();
+                let id = self.alloc_handle(Handle::Read { name, pos: 0 });
+                out.push(id);
+                out.extend(u32::try_from(len).unwrap().to_le_bytes());
+            }
+            Opcode::SystemRead => {
+                let id = req.read_u8().unwrap();
+                let count = usize::from(req.read_u16().unwrap());
+                let Some(Handle::Read { name, pos }) =
+                    self.handles.get_mut(&id)
+                else {
+                    return Err(DeviceError::IllegalHandle);
+                };
+                let data = &self.files[name].data;
+                let end = data.len().min(*pos + count);
+                let chunk = &data[*pos..end];
+                *pos = end;
+                out.push(id);
+                out.extend(u16::try_from(chunk.len()).unwrap().to_le_bytes());
+                out.extend(chunk);
+            }
+            _ => return self.handle_write(req),
+        }
+        Ok(out)
+    }
+
+    /// Handle a request which creates, modifies or removes a file
+    fn handle_write(&mut self, req: &mut Packet) -> Reply {
+        let mut out = Vec::new();
+        match req.opcode {
+            Opcode::SystemOpenwrite
+            | Opcode::SystemOpenwritelinear
+            | Opcode::SystemOpenwritedata => {
+                let name = req.read_filename().unwrap();
+                let len = req.read_u32().unwrap();
+                if self.files.contains_key(&name) {
+                    return Err(DeviceError::FileExists);
+                }
+                if len > self.flash {
+                    return Err(DeviceError::NoSpace);
+                }
+                let mode = match req.opcode {
+                    Opcode::SystemOpenwritelinear => WriteMode::Linear,
+                    Opcode::SystemOpenwritedata => WriteMode::Data,
+                    _ => WriteMode::Normal,
+                };
+                let id = self.alloc_handle(Handle::Write {
+                    name,
+                    data: Vec::new(),
+                    mode,
+                });
+                out.push(id);
+            }
+            Opcode::SystemWrite => {
+                let id = req.read_u8().unwrap();
+                let Some(Handle::Write { data, .. }) =
+                    self.handles.get_mut(&id)
+                else {
+                    return Err(DeviceError::IllegalHandle);
+                };
+                // request is type, opcode, handle, then the data
+                let chunk = req.read_slice(req.data.len() - 3).unwrap();
+                let start = data.len();
+                data.extend(chunk);
+                if self.corrupt_writes > 0 {
+                    self.corrupt_writes -= 1;
+                    data[start] ^= 0xff;
+                }
+                out.push(id);
+                out.extend(u16::try_from(chunk.len()).unwrap().to_le_bytes());
+            }
+            Opcode::SystemClose => {
+                let id = req.read_u8().unwrap();
+                match self.handles.remove(&id) {
+                    Some(Handle::Write { name, data, mode }) => {
+                        self.flash -= u32::try_from(data.len()).unwrap();
+                        self.files.insert(name, MockFile { data, mode });
+                    }
+                    Some(_) => {}
+                    None => return Err(DeviceError::HandleAlreadyClosed),
+                }
+                out.push(id);
+            }
+            Opcode::SystemDelete => {
+                let name = req.read_filename().unwrap();
+                let file = self
+                    .files
+                    .remove(&name)
+                    .ok_or(DeviceError::FileNotFound)?;
+                self.flash += u32::try_from(file.data.len()).unwrap();
+                push_filename(&mut out, &name);
+            }
+            _ => return self.handle_module(req),
+        }
+        Ok(out)
+    }
+
+    /// Handle a request which accesses a module
+    fn handle_module(&mut self, req: &mut Packet) -> Reply {
+        let mut out = Vec::new();
+        match req.opcode {
+            Opcode::SystemIomapread => {
+                let id = req.read_u32().unwrap();
+                let offset = usize::from(req.read_u16().unwrap());
+                let count = usize::from(req.read_u16().unwrap());
+                let iomap =
+                    self.iomaps.get(&id).ok_or(DeviceError::ModuleNotFound)?;
+                let data = iomap
+                    .get(offset..offset + count)
+                    .ok_or(DeviceError::OutOfBounds)?;
+                out.extend(id.to_le_bytes());
+                out.extend(u16::try_from(count).unwrap().to_le_bytes());
+                out.extend(data);
+            }
+            Opcode::SystemIomapwrite => {
+                let id = req.read_u32().unwrap();
+                let offset = usize::from(req.read_u16().unwrap());
+                let count = usize::from(req.read_u16().unwrap());
+                let data = req.read_slice(count).unwrap();
+                let iomap = self
+                    .iomaps
+                    .get_mut(&id)
+                    .ok_or(DeviceError::ModuleNotFound)?;
+                iomap
+                    .get_mut(offset..offset + count)
+                    .ok_or(DeviceError::OutOfBounds)?
+                    .copy_from_slice(data);
+                out.extend(id.to_le_bytes());
+                out.extend(u16::try_from(count).unwrap().to_le_bytes());
+            }
+            Opcode::SystemFindfirstmodule => {
+                let pattern = req.read_filename().unwrap();
+                let mut matches = self
+                    .modules
+                    .keys()
+                    .filter(|name| matches_pattern(&pattern, name))
+                    .cloned()
+                    .collect::<VecDeque<_>>();
+                let name =
+                    matches.pop_front().ok_or(DeviceError::ModuleNotFound)?;
+                let id = self.alloc_handle(Handle::FindModule { matches });
+                out.push(id);
+                self.push_module(&mut out, &name);
+            }
+            Opcode::SystemFindnextmodule => {
+                let id = req.read_u8().unwrap();
+                let Some(Handle::FindModule { matches }) =
+                    self.handles.get_mut(&id)
+                else {
+                    return Err(DeviceError::IllegalHandle);
+                };
+                let name =
+                    matches.pop_front().ok_or(DeviceError::ModuleNotFound)?;
+                out.push(id);
+                self.push_module(&mut out, &name);
+            }
+            Opcode::SystemClosemodhandle => {
+                let id = req.read_u8().unwrap();
+                self.handles
+                    .remove(&id)
+                    .ok_or(DeviceError::HandleAlreadyClosed)?;
+                out.push(id);
+            }
+            _ => return self.handle_program(req),
+        }
+        Ok(out)
+    }
+
+    /// Handle a request which starts or stops a program. The program's
+    /// status is shown in the command module's iomap, if there is one;
+    /// tests update it to simulate the program running.
+    fn handle_program(&mut self, req: &mut Packet) -> Reply {
+        let status = usize::from(command::PROG_STATUS.offset);
+        let name_offset = usize::from(command::FILE_NAME.offset);
+        let iomap = self.iomaps.get_mut(&command::MODULE.id);
+        match req.opcode {
+            Opcode::DirectStartProgram => {
+                let name = req.read_filename().unwrap();
+                if !self.files.contains_key(&name) {
+                    return Err(DeviceError::FileNotFound);
+                }
+                if let Some(iomap) = iomap {
+                    let state =
+                        self.program_exit.unwrap_or(ProgStatus::Running);
+                    iomap[status] = state as u8;
+                    iomap[name_offset..name_offset + FILENAME_LEN].fill(0);
+                    iomap[name_offset..name_offset + name.len()]
+                        .copy_from_slice(name.as_bytes());
+                }
+            }
+            Opcode::DirectStopProgram => match iomap {
+                Some(iomap) if iomap[status] == ProgStatus::Running as u8 => {
+                    iomap[status] = ProgStatus::Abort as u8;
+                }
+                _ => return Err(DeviceError::NoActiveProgram),
+            },
+            _ => return self.handle_poll(req),
+        }
+        Ok(Vec::new())
+    }
+
+    /// Handle a request which reads a poll buffer
+    fn handle_poll(&mut self, req: &mut Packet) -> Reply {
+        let mut out = Vec::new();
+        match req.opcode {
+            Opcode::SystemPollcmdlen => {
+                let buf = req.read_u8().unwrap();
+                let len = self.poll_bufs[usize::from(buf)].len();
+                out.push(buf);
+                out.push(u8::try_from(len).unwrap_or(u8::MAX));
+            }
+            Opcode::SystemPollcmd => {
+                let buf = req.read_u8().unwrap();
+                let len = usize::from(req.read_u8().unwrap());
+                let poll_buf = &mut self.poll_bufs[usize::from(buf)];
+                let data = poll_buf
+                    .drain(..len.min(poll_buf.len()))
+                    .collect::<Vec<_>>();
+                out.push(buf);
+                out.push(u8::try_from(data.len()).unwrap());
+                out.extend(data);
+            }
+            _ => return self.handle_message(req),
+        }
+        Ok(out)
+    }
+
+    /// Handle a request which accesses a mailbox
+    fn handle_message(&mut self, req: &mut Packet) -> Reply {
+        let mut out = Vec::new();
+        match req.opcode {
+            Opcode::DirectMessageWrite => {
+                let inbox = req.read_u8().unwrap();
+                let len = usize::from(req.read_u8().unwrap());
+                let data = req.read_slice(len).unwrap().to_vec();
+                self.mailboxes.entry(inbox).or_default().push_back(data);
+            }
+            Opcode::DirectMessageRead => {
+                let remote_inbox = req.read_u8().unwrap();
+                let local_inbox = req.read_u8().unwrap();
+                let remove = req.read_bool().unwrap();
+                let queue = self.mailboxes.entry(remote_inbox).or_default();
+                let data = if remove {
+                    queue.pop_front()
+                } else {
+                    queue.front().cloned()
+                }
+                .ok_or(DeviceError::QueueEmpty)?;
+                out.push(local_inbox);
+                out.push(u8::try_from(data.len()).unwrap());
+                out.extend(data);
+            }
+            _ => return Err(DeviceError::UnknownCommand),
+        }
+        Ok(out)
+    }
+
+    /// Append the name, ID and sizes of a module search result
+    fn push_module(&self, out: &mut Vec<u8>, name: &str) {
+        let id = self.modules[name];
+        let iomap_len = u16::try_from(self.iomaps[&id].len()).unwrap();
+        push_filename(out, name);
+        out.extend(id.to_le_bytes());
+        // module size is not simulated
+        out.extend(0u32.to_le_bytes());
+        out.extend(iomap_len.to_le_bytes());
+    }
+
+    /// Append the name and length of a file search result
+    fn push_find_result(&self, out: &mut Vec<u8>, name: &str) {
+        push_filename(out, name);
+        let len = u32::try_from(self.files[name].data.len()).unwrap();
+        out.extend(len.to_le_bytes());
+    }
+}
+
+/// Append a null-padded filename field
+fn push_filename(out: &mut Vec<u8>, name: &str) {
+    let mut name = name.as_bytes().to_vec();
+    name.resize(FILENAME_LEN, 0);
+    out.extend(name);
+}
+
+/// Match a filename against an NXT search pattern such as `*.*`,
+/// `*.rxe` or `prog.*`
+fn matches_pattern(pattern: &str, name: &str) -> bool {
+    let part_matches = |pat: &str, part: &str| pat == "*" || pat == part;
+    match (pattern.split_once('.'), name.split_once('.')) {
+        (Some((pat_stem, pat_ext)), Some((stem, ext))) => {
+            part_matches(pat_stem, stem) && part_matches(pat_ext, ext)
+        }
+        _ => pattern == name,
+    }
+}
+
+/// Socket connected to a simulated [`Brick`]
+pub struct Mock {
+    /// Shared brick state
+    brick: Arc<Mutex<Brick>>,
+    /// Serialised replies waiting to be received
+    replies: Mutex<VecDeque<Vec<u8>>>,
+}
+
+impl Mock {
+    /// Create a socket connected to the provided brick
+    pub fn new(brick: &Arc<Mutex<Brick>>) -> Self {
+        Self {
+            brick: Arc::clone(brick),
+            replies: Mutex::new(VecDeque::new()),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Socket for Mock {
+    async fn send(&self, data: &[u8]) -> Result<usize> {
+        let mut req = Packet::parse(data)?;
+        let opcode = req.opcode;
+        let reply = self.brick.lock().unwrap().handle(&mut req);
+        let mut buf = vec![0x02, opcode as u8];
+        match reply {
+            Ok(payload) => {
+                buf.push(DeviceError::None as u8);
+                buf.extend(payload);
+            }
+            Err(err) => buf.push(err as u8),
+        }
+        self.replies.lock().unwrap().push_back(buf);
+        Ok(data.len())
+    }
+
+    async fn recv<'buf>(&self, buf: &'buf mut [u8]) -> Result<&'buf [u8]> {
+        let reply = self.replies.lock().unwrap().pop_front().unwrap();
+        buf[..reply.len()].copy_from_slice(&reply);
+        Ok(&buf[..reply.len()])
+    }
+}
+
+/// Create a simulated brick and an `Nxt` connected to it
+pub async fn connect(brick: Brick) -> (crate::Nxt, Arc<Mutex<Brick>>) {
+    let brick = Arc::new(Mutex::new(brick));
+    let nxt = crate::Nxt::init(Mock::new(&brick)).await.unwrap();
+    (nxt, brick)
+}
@@ -0,0 +1,276 @@
+//! Conversion between PCM audio and the NXT `.rso` sound file format
+
+use crate::{Error, Nxt, Result};
+
+/// Format code for uncompressed 8-bit sampled sound
+const FORMAT_SAMPLED: u16 = 0x0100;
+/// Format code for ADPCM compressed sound (not supported)
+const FORMAT_ADPCM: u16 = 0x0101;
+/// Length of the RSO file header
+const RSO_HEADER_LEN: usize = 8;
+/// Lowest sample rate supported by the firmware, in Hz
+pub const MIN_SAMPLE_RATE: u16 = 2000;
+/// Highest sample rate supported by the firmware, in Hz
+pub const MAX_SAMPLE_RATE: u16 = 16000;
+/// WAV format code for integer PCM data
+const WAV_FORMAT_PCM: u16 = 1;
+
+/// An 8-bit mono sound, as stored in an `.rso` file
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Sound {
+    /// Sample rate, in Hz
+    pub sample_rate: u16,
+    /// Unsigned 8-bit samples, centred on `0x80`
+    pub samples: Vec<u8>,
+}
+
+impl Sound {
+    /// Parse the contents of an `.rso` file. Only uncompressed sound
+    /// files are supported.
+    pub fn from_rso(data: &[u8]) -> Result<Self> {
+        let header = data
+            .get(..RSO_HEADER_LEN)
+            .ok_or(Error::Parse("RSO header too short"))?;
+        let word = |idx: usize| {
+            u16::from_be_bytes([header[idx * 2], header[idx * 2 + 1]])
+        };
+        match word(0) {
+            FORMAT_SAMPLED => {}
+            FORMAT_ADPCM => {
+                return Err(Error::Parse("Compressed RSO not supported"))
+            }
+            _ => return Err(Error::Parse("Invalid RSO format")),
+        }
+        let len = usize::from(word(1));
+        let sample_rate = word(2);
+        let samples = data
+            .get(RSO_HEADER_LEN..RSO_HEADER_LEN + len)
+            .ok_or(Error::Parse("RSO data too short"))?
+            .to_vec();
+
+        Ok(Self {
+            sample_rate,
+            samples,
+        })
+    }
+
+    /// Serialise the sound into the `.rso` file format. Returns an error
+    /// if there are more samples than can be described by the header.
+    pub fn to_rso(&self) -> Result<Vec<u8>> {
+        let len = u16::try_from(self.samples.len())?;
+        let mut out = Vec::with_capacity(RSO_HEADER_LEN + self.samples.len());
+        out.extend(FORMAT_SAMPLED.to_be_bytes());
+        out.extend(len.to_be_bytes());
+        out.extend(self.sample_rate.to_be_bytes());
+        // play mode; unused by the firmware
+        out.extend(0u16.to_be_bytes());
+        out.extend(&self.samples);
+        Ok(out)
+    }
+
+    /// Convert signed 16-bit mono PCM into a sound, resampling to the
+    /// nearest rate supported by the firmware if necessary
+    #[must_use]
+    pub fn from_pcm(samples: &[i16], sample_rate: u32) -> Self {
+        let target =
+            sample_rate.clamp(MIN_SAMPLE_RATE.into(), MAX_SAMPLE_RATE.into());
+        let samples = resample(samples, sample_rate, target)
+            .into_iter()
+            .map(|sample| (sample.to_be_bytes()[0]) ^ 0x80)
+            .collect();
+        Self {
+            // clamped into range above
+            sample_rate: target.try_into().unwrap(),
+            samples,
+        }
+    }
+
+    /// Convert the sound into signed 16-bit mono PCM at its own sample
+    /// rate
+    #[must_use]
+    pub fn to_pcm(&self) -> Vec<i16> {
+        self.samples
+            .iter()
+            .map(|&sample| i16::from_be_bytes([sample ^ 0x80, 0]))
+            .collect()
+    }
+
+    /// Parse a WAV file containing 8- or 16-bit integer PCM data and
+    /// convert it to a sound. Multiple channels are mixed down to mono.
+    pub fn from_wav(data: &[u8]) -> Result<Self> {
+        let (format, pcm) = parse_wav(data)?;
+        if format.format != WAV_FORMAT_PCM {
+            return Err(Error::Parse("Only PCM WAV files are supported"));
+        }
+        let channels = usize::from(format.channels);
+        if channels == 0 {
+            return Err(Error::Parse("WAV file has no channels"));
+        }
+
+        let samples = match format.bits_per_sample {
+            8 => pcm
+                .iter()
+                .map(|&sample| i16::from_be_bytes([sample ^ 0x80, 0]))
+                .collect::<Vec<_>>(),
+            16 => pcm
+                .chunks_exact(2)
+                .map(|pair| i16::from_le_bytes([pair[0], pair[1]]))
+                .collect(),
+            _ => return Err(Error::Parse("Unsupported WAV sample size")),
+        };
+        let mono = samples
+            .chunks_exact(channels)
+            .map(|frame| {
+                let sum = frame.iter().copied().map(i32::from).sum::<i32>();
+                // the mean of i16 values always fits in an i16
+                i16::try_from(sum / i32::from(format.channels)).unwrap()
+            })
+            .collect::<Vec<_>>();
+
+        Ok(Self::from_pcm(&mono, format.sample_rate))
+    }
+}
+
+/// Relevant fields of a WAV `fmt ` chunk
+struct WavFormat {
+    /// Audio format code
+    format: u16,
+    /// Number of interleaved channels
+    channels: u16,
+    /// Sample rate, in Hz
+    sample_rate: u32,
+    /// Size of a single sample
+    bits_per_sample: u16,
+}
+
+/// Split a WAV file into its format description and raw sample data
+fn parse_wav(data: &[u8]) -> Result<(WavFormat, &[u8])> {
+    if data.len() < 12 || &data[..4] != b"RIFF" || &data[8..12] != b"WAVE" {
+        return Err(Error::Parse("Not a WAV file"));
+    }
+
+    let mut format = None;
+    let mut rest = &data[12..];
+    while rest.len() >= 8 {
+        let id = &rest[..4];
+        let len = u32::from_le_bytes([rest[4], rest[5], rest[6], rest[7]]);
+        let body = rest
+            .get(8..8 + usize::try_from(len)?)
+            .ok_or(Error::Parse("WAV chunk too short"))?;
+        match id {
+            b"fmt " if body.len() >= 16 => {
+                let word =
+                    |idx: usize| u16::from_le_bytes([body[idx], body[idx + 1]]);
+                format = Some(WavFormat {
+                    format: word(0),
+                    channels: word(2),
+                    sample_rate: u32::from_le_bytes([
+                        body[4], body[5], body[6], body[7],
+                    ]),
+                    bits_per_sample: word(14),
+                });
+            }
+            b"data" => {
+                let format =
+                    format.ok_or(Error::Parse("WAV data before format"))?;
+                return Ok((format, body));
+            }
+            _ => {}
+        }
+        // chunks are padded to an even length
+        let padded = body.len() + body.len() % 2;
+        rest = rest.get(8 + padded..).unwrap_or_default();
+    }
+
+    Err(Error::Parse("WAV file has no data"))
+}
+
+/// Resample using linear interpolation between neighbouring samples
+fn resample(samples: &[i16], from: u32, to: u32) -> Vec<i16> {
+    if from == to || from == 0 || samples.is_empty() {
+        return samples.to_vec();
+    }
+
+    let out_len = samples.len() as u64 * u64::from(to) / u64::from(from);
+    (0..out_len)
+        .map(|idx| {
+            // position in the source, in units of 1/`to` samples
+            let pos = idx * u64::from(from);
+            let base = usize::try_from(pos / u64::from(to)).unwrap();
+            let frac = i64::try_from(pos % u64::from(to)).unwrap();
+            let a = i64::from(samples[base]);
+            let b = i64::from(*samples.get(base + 1).unwrap_or(&samples[base]));
+            let value = a + (b - a) * frac / i64::from(to);
+            // interpolated between two i16 values
+            i16::try_from(value).unwrap()
+        })
+        .collect()
+}
+
+impl Nxt {
+    /// Convert a WAV file into the `.rso` format and upload it to the
+    /// brick under the given name, ready for [`Nxt::play_sound`]
+    pub async fn upload_sound(&self, name: &str, wav: &[u8]) -> Result<()> {
+        let rso = Sound::from_wav(wav)?.to_rso()?;
+        self.upload_file(name, &rso).await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Build a WAV file from the given parameters and raw sample data
+    fn wav(channels: u16, rate: u32, bits: u16, data: &[u8]) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend(b"RIFF");
+        out.extend((36 + u32::try_from(data.len()).unwrap()).to_le_bytes());
+        out.extend(b"WAVEfmt ");
+        out.extend(16u32.to_le_bytes());
+        out.extend(WAV_FORMAT_PCM.to_le_bytes());
+        out.extend(channels.to_le_bytes());
+        out.extend(rate.to_le_bytes());
+        out.extend((rate * u32::from(channels * bits / 8)).to_le_bytes());
+        out.extend((channels * bits / 8).to_le_bytes());
+        out.extend(bits.to_le_bytes());
+        out.extend(b"data");
+        out.extend(u32::try_from(data.len()).unwrap().to_le_bytes());
+        out.extend(data);
+        out
+    }
+
+    #[test]
+    fn rso_roundtrip() {
+        let sound = Sound {
+            sample_rate: 8000,
+            samples: vec![0x80, 0xff, 0x00, 0x40],
+        };
+        let rso = sound.to_rso().unwrap();
+        assert_eq!(rso, [1, 0, 0, 4, 0x1f, 0x40, 0, 0, 0x80, 0xff, 0x00, 0x40]);
+        assert_eq!(Sound::from_rso(&rso).unwrap(), sound);
+        assert_eq!(sound.to_pcm(), [0, 0x7f00, -0x8000, -0x4000]);
+
+        Sound::from_rso(&rso[..10]).unwrap_err();
+    }
+
+    #[test]
+    fn wav_stereo_16bit() {
+        // two frames: (max, max) and (min, 0)
+        let data = [0xff, 0x7f, 0xff, 0x7f, 0x00, 0x80, 0x00, 0x00];
+        let sound = Sound::from_wav(&wav(2, 8000, 16, &data)).unwrap();
+        assert_eq!(sound.sample_rate, 8000);
+        assert_eq!(sound.samples, [0xff, 0x40]);
+    }
+
+    #[test]
+    fn wav_resampled() {
+        let data = [0x80, 0x90, 0xa0, 0xb0, 0xc0, 0xd0, 0xe0, 0xf0];
+        let sound = Sound::from_wav(&wav(1, 32000, 8, &data)).unwrap();
+        assert_eq!(sound.sample_rate, MAX_SAMPLE_RATE);
+        assert_eq!(sound.samples, [0x80, 0xa0, 0xc0, 0xe0]);
+
+        let sound = Sound::from_wav(&wav(1, 1000, 8, &data[..2])).unwrap();
+        assert_eq!(sound.sample_rate, MIN_SAMPLE_RATE);
+        assert_eq!(sound.samples, [0x80, 0x88, 0x90, 0x90]);
+    }
+}
@@ -2,6 +2,9 @@
 
 use crate::{DISPLAY_DATA_LEN, DISPLAY_HEIGHT, DISPLAY_WIDTH};
 
+/// File search pattern matching every file on the brick
+pub const ALL_FILES: &str = "*.*";
+
 /// Handle identifying an open file on the NXT brick
 #[derive(Debug)]
 pub struct FileHandle {
@@ -22,6 +25,94 @@ pub struct FindFileHandle {
     pub len: u32,
 }
 
+/// Name and size of a file stored on the brick, as returned by
+/// [`Nxt::list_files`](crate::Nxt::list_files)
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FileInfo {
+    /// Name of the file
+    pub name: String,
+    /// Length of the file, in bytes
+    pub len: u32,
+}
+
+impl FileInfo {
+    /// Type of the file, based on its extension
+    #[must_use]
+    pub fn file_type(&self) -> FileType {
+        FileType::from_name(&self.name)
+    }
+}
+
+/// Description of a firmware module, as returned by
+/// [`Nxt::list_modules`](crate::Nxt::list_modules)
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ModuleInfo {
+    /// Name of the module, e.g. `Display.mod`
+    pub name: String,
+    /// ID of the module, used to access its iomap
+    pub id: u32,
+    /// Size of the module
+    pub len: u32,
+    /// Length of the module's iomap
+    pub iomap_len: u16,
+}
+
+/// Kinds of file stored on the brick, identified by their extension
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FileType {
+    /// Executable program (`.rxe`, `.rtm`, `.sys`)
+    Executable,
+    /// Graphics file (`.ric`)
+    Image,
+    /// Sound file (`.rso`)
+    Sound,
+    /// Data file written by a program or datalog (`.rdt`, `.log`)
+    Data,
+    /// Anything else
+    Other,
+}
+
+impl FileType {
+    /// Determine the file type from the extension of the provided name
+    #[must_use]
+    pub fn from_name(name: &str) -> Self {
+        let ext = name
+            .rsplit_once('.')
+            .map(|(_, ext)| ext.to_ascii_lowercase())
+            .unwrap_or_default();
+        match ext.as_str() {
+            "rxe" | "rtm" | "sys" => Self::Executable,
+            "ric" => Self::Image,
+            "rso" => Self::Sound,
+            "rdt" | "log" => Self::Data,
+            _ => Self::Other,
+        }
+    }
+
+    /// The mode a file of this type must be opened with when writing
+    /// it to the brick. Executables and graphics are accessed in place
+    /// by the firmware, so must be stored contiguously.
+    #[must_use]
+    pub const fn write_mode(self) -> WriteMode {
+        match self {
+            Self::Executable | Self::Image => WriteMode::Linear,
+            Self::Data => WriteMode::Data,
+            Self::Sound | Self::Other => WriteMode::Normal,
+        }
+    }
+}
+
+/// Ways in which a file can be opened for writing
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum WriteMode {
+    /// Regular file, may be fragmented in flash
+    Normal,
+    /// File stored contiguously in flash
+    Linear,
+    /// Data file which may later be appended to
+    Data,
+}
+
 /// Version information from the NXT brick
 #[derive(Debug)]
 pub struct FwVersion {
@@ -98,6 +189,26 @@ pub fn display_data_to_raster(data: &[u8; DISPLAY_DATA_LEN]) -> DisplayRaster {
     out
 }
 
+/// Function to map a rectangular array back onto the column-major
+/// display iomap format; the inverse of [`display_data_to_raster`]. Any
+/// non-zero pixel is treated as set.
+#[must_use]
+pub fn raster_to_display_data(
+    raster: &DisplayRaster,
+) -> [u8; DISPLAY_DATA_LEN] {
+    let mut out = [0u8; DISPLAY_DATA_LEN];
+    for (idx, chunk) in out.iter_mut().enumerate() {
+        let col = idx % DISPLAY_WIDTH;
+        let row_base = (idx / DISPLAY_WIDTH) * 8;
+        for shift in 0..8 {
+            if raster[row_base + shift][col] != 0 {
+                *chunk |= 1 << shift;
+            }
+        }
+    }
+    out
+}
+
 /// Render the display raster into a string for printing to the terminal
 #[must_use]
 pub fn raster_to_string(raster: &DisplayRaster) -> String {
@@ -230,5 +341,12 @@ mod test {
         let rendered = raster_to_string(&raster);
         println!("{rendered}");
         assert_eq!(rendered, DISPLAY_RENDERED);
+        assert_eq!(raster_to_display_data(&raster), DISPLAY_DATA);
+    }
+
+    #[test]
+    fn display_text() {
+        let raster = display_data_to_raster(&DISPLAY_DATA);
+        assert!(crate::font::screen_contains(&raster, "My Files"));
     }
 }
@@ -0,0 +1,376 @@
+//! Status of the bytecode interpreter, read from the command module's
+//! iomap.
+//!
+//! The firmware records whether the last program finished normally,
+//! stopped with an error or was aborted, but not which error occurred;
+//! the brick shows "File error!" in that case. Nor does it have a
+//! paused state, which only some third-party firmware supports.
+
+use crate::{
+    iomap::{command, FieldValue},
+    poll::{poll_stream, Poller},
+    DeviceError, Error, Nxt, Result,
+};
+pub use command::ProgStatus;
+use futures::Stream;
+use std::time::Duration;
+use tokio::{
+    runtime::Handle,
+    time::{self, Interval},
+};
+
+/// Interval at which [`Nxt::run_program`] polls the interpreter status
+pub const RUN_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Status of the bytecode interpreter
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VmStatus {
+    /// Name of the running program, or of the last one to run
+    pub program: Option<String>,
+    /// State of the interpreter
+    pub state: ProgStatus,
+    /// System tick when the status was read, in milliseconds
+    pub tick: u32,
+}
+
+impl VmStatus {
+    /// Whether a program is running
+    #[must_use]
+    pub fn is_running(&self) -> bool {
+        self.state == ProgStatus::Running
+    }
+
+    /// Whether two statuses differ other than in their tick
+    fn changed_from(&self, other: &Self) -> bool {
+        self.state != other.state || self.program != other.program
+    }
+}
+
+/// How a program run by [`Nxt::run_program`] ended
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    /// The program exited normally
+    Finished,
+    /// The program was stopped from the brick or another connection
+    Aborted,
+    /// The interpreter stopped the program because of an error
+    Error,
+    /// The program ran for longer than the timeout, and was stopped
+    TimedOut,
+}
+
+/// State carried between statuses of [`Nxt::watch_vm`]
+struct Watcher {
+    /// Connection to the brick
+    nxt: Nxt,
+    /// The last status yielded
+    last: Option<VmStatus>,
+}
+
+impl Poller for Watcher {
+    type Item = VmStatus;
+
+    /// Wait for the state or program to change from the last status
+    async fn next(&mut self, interval: &mut Interval) -> Result<VmStatus> {
+        loop {
+            interval.tick().await;
+            let status = self.nxt.get_vm_status().await?;
+            if self
+                .last
+                .as_ref()
+                .is_none_or(|last| status.changed_from(last))
+            {
+                self.last = Some(status.clone());
+                return Ok(status);
+            }
+        }
+    }
+}
+
+/// Stops the running program when dropped, unless disarmed, so that
+/// cancelling [`Nxt::run_program`] doesn't leave it running
+struct StopOnDrop(Option<Nxt>);
+
+impl Drop for StopOnDrop {
+    fn drop(&mut self) {
+        let (Some(nxt), Ok(runtime)) = (self.0.take(), Handle::try_current())
+        else {
+            return;
+        };
+        runtime.spawn(async move {
+            // the program may have stopped by itself in the meantime
+            let _ = nxt.stop_program().await;
+        });
+    }
+}
+
+impl Nxt {
+    /// Read the status of the bytecode interpreter, in a single request
+    pub async fn get_vm_status(&self) -> Result<VmStatus> {
+        // the tick, status and file name are close together, so read the
+        // whole range at once
+        let start = command::TICK.offset;
+        let end = command::FILE_NAME.offset + command::FILE_NAME.len;
+        let id = self.module_id(command::MODULE.name).await?;
+        let data = self.read_io_map_range(id, start, end - start).await?;
+        let field = |offset: u16| &data[usize::from(offset - start)..];
+
+        let program = String::decode(field(command::FILE_NAME.offset))?;
+        Ok(VmStatus {
+            program: (!program.is_empty()).then_some(program),
+            state: ProgStatus::decode(field(command::PROG_STATUS.offset))?,
+            tick: u32::decode(field(command::TICK.offset))?,
+        })
+    }
+
+    /// Poll the interpreter status at the given interval, yielding the
+    /// current status and then each time the state or program changes.
+    /// Errors are yielded without ending the stream; stop polling by
+    /// dropping it.
+    ///
+    /// See [`MIN_POLL_INTERVAL`](crate::MIN_POLL_INTERVAL) for the
+    /// shortest interval and the runtime needed.
+    pub fn watch_vm(
+        &self,
+        interval: Duration,
+    ) -> impl Stream<Item = Result<VmStatus>> + Send + 'static {
+        let watcher = Watcher {
+            nxt: self.clone(),
+            last: None,
+        };
+        poll_stream(interval, watcher)
+    }
+
+    /// Start a program and wait for it to exit, up to the timeout if one
+    /// is given, at which point it is stopped. If this future is dropped
+    /// before the program exits, the program is stopped in the
+    /// background.
+    ///
+    /// The firmware soon resets the interpreter status after a program
+    /// exits, so an exit is reported as [`Outcome::Finished`] when the
+    /// status is missed. Requires a Tokio runtime with the timer enabled.
+    pub async fn run_program(
+        &self,
+        name: &str,
+        timeout: Option<Duration>,
+    ) -> Result<Outcome> {
+        self.start_program(name).await?;
+        let mut guard = StopOnDrop(Some(self.clone()));
+
+        let exit = self.wait_for_exit();
+        let outcome = match timeout {
+            None => exit.await?,
+            Some(timeout) => {
+                if let Ok(outcome) = time::timeout(timeout, exit).await {
+                    outcome?
+                } else {
+                    match self.stop_program().await {
+                        Ok(()) => Outcome::TimedOut,
+                        // it exited by itself before it could be stopped
+                        Err(Error::Device(DeviceError::NoActiveProgram)) => {
+                            let state =
+                                self.read_field(command::PROG_STATUS).await?;
+                            exit_outcome(state).unwrap_or(Outcome::Finished)
+                        }
+                        Err(e) => return Err(e),
+                    }
+                }
+            }
+        };
+        guard.0 = None;
+        Ok(outcome)
+    }
+
+    /// Upload a program, replacing any existing file of the same name,
+    /// then run it as with [`Nxt::run_program`]
+    pub async fn upload_and_run_program(
+        &self,
+        name: &str,
+        data: &[u8],
+        timeout: Option<Duration>,
+    ) -> Result<Outcome> {
+        self.upload_program(name, data).await?;
+        self.run_program(name, timeout).await
+    }
+
+    /// Poll the interpreter until the program which has just been
+    /// started exits
+    async fn wait_for_exit(&self) -> Result<Outcome> {
+        // the interpreter starts the program within a tick of the
+        // request, so from the first poll on, any state other than
+        // running means that it has exited
+        let first = time::Instant::now() + RUN_POLL_INTERVAL;
+        let mut interval = time::interval_at(first, RUN_POLL_INTERVAL);
+        loop {
+            interval.tick().await;
+            let state = self.read_field(command::PROG_STATUS).await?;
+            if let Some(outcome) = exit_outcome(state) {
+                return Ok(outcome);
+            }
+        }
+    }
+}
+
+/// How a program ended, given the interpreter state after it was
+/// started, or `None` if it is still running
+const fn exit_outcome(state: ProgStatus) -> Option<Outcome> {
+    match state {
+        ProgStatus::Running => None,
+        ProgStatus::Idle | ProgStatus::Reset | ProgStatus::Ok => {
+            Some(Outcome::Finished)
+        }
+        ProgStatus::Abort => Some(Outcome::Aborted),
+        ProgStatus::Error => Some(Outcome::Error),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        socket::mock::{self, Brick},
+        system::WriteMode,
+    };
+    use futures::StreamExt;
+    use std::sync::{Arc, Mutex};
+
+    /// Connect to a brick with a command module and a program to run
+    async fn program_brick() -> (Nxt, Arc<Mutex<Brick>>) {
+        let mut brick = Brick::default();
+        brick.add_iomap(command::MODULE, 64);
+        brick.add_file("prog.rxe", &[], WriteMode::Normal);
+        mock::connect(brick).await
+    }
+
+    /// Set the interpreter state shown by the brick
+    fn set_state(brick: &Arc<Mutex<Brick>>, state: ProgStatus) {
+        let offset = usize::from(command::PROG_STATUS.offset);
+        brick
+            .lock()
+            .unwrap()
+            .iomaps
+            .get_mut(&command::MODULE.id)
+            .unwrap()[offset] = state as u8;
+    }
+
+    /// Read the interpreter state shown by the brick
+    fn get_state(brick: &Arc<Mutex<Brick>>) -> ProgStatus {
+        let offset = usize::from(command::PROG_STATUS.offset);
+        let state = brick.lock().unwrap().iomaps[&command::MODULE.id][offset];
+        state.try_into().unwrap()
+    }
+
+    #[tokio::test]
+    async fn run_program() {
+        let (nxt, brick) = program_brick().await;
+        let brick = &brick;
+        let finish = |state| async move {
+            time::sleep(RUN_POLL_INTERVAL * 2).await;
+            set_state(brick, state);
+        };
+
+        let (outcome, ()) = tokio::join!(
+            nxt.run_program("prog.rxe", None),
+            finish(ProgStatus::Ok)
+        );
+        assert_eq!(outcome.unwrap(), Outcome::Finished);
+
+        let (outcome, ()) = tokio::join!(
+            nxt.run_program("prog.rxe", None),
+            finish(ProgStatus::Error)
+        );
+        assert_eq!(outcome.unwrap(), Outcome::Error);
+
+        nxt.run_program("missing.rxe", None).await.unwrap_err();
+    }
+
+    #[tokio::test]
+    async fn quick_exit() {
+        let (nxt, brick) = program_brick().await;
+        // the firmware resets the state before the first poll
+        brick.lock().unwrap().program_exit = Some(ProgStatus::Idle);
+        let outcome = nxt.run_program("prog.rxe", None).await.unwrap();
+        assert_eq!(outcome, Outcome::Finished);
+
+        // the timeout expires first, but the program has exited by the
+        // time it would be stopped
+        let outcome = nxt
+            .run_program("prog.rxe", Some(RUN_POLL_INTERVAL / 2))
+            .await
+            .unwrap();
+        assert_eq!(outcome, Outcome::Finished);
+
+        brick.lock().unwrap().program_exit = Some(ProgStatus::Error);
+        let outcome = nxt.run_program("prog.rxe", None).await.unwrap();
+        assert_eq!(outcome, Outcome::Error);
+    }
+
+    #[tokio::test]
+    async fn stop_program() {
+        let (nxt, brick) = program_brick().await;
+
+        let outcome = nxt
+            .run_program("prog.rxe", Some(RUN_POLL_INTERVAL * 2))
+            .await
+            .unwrap();
+        assert_eq!(outcome, Outcome::TimedOut);
+        assert_eq!(get_state(&brick), ProgStatus::Abort);
+
+        // cancelling the run stops the program
+        set_state(&brick, ProgStatus::Idle);
+        time::timeout(RUN_POLL_INTERVAL * 2, nxt.run_program("prog.rxe", None))
+            .await
+            .unwrap_err();
+        assert_eq!(get_state(&brick), ProgStatus::Running);
+        time::sleep(RUN_POLL_INTERVAL).await;
+        assert_eq!(get_state(&brick), ProgStatus::Abort);
+    }
+
+    #[tokio::test]
+    async fn status() {
+        let mut brick = Brick::default();
+        brick.add_iomap(command::MODULE, 64);
+        let (nxt, state) = mock::connect(brick).await;
+        let set = |offset: u16, data: &[u8]| {
+            let offset = usize::from(offset);
+            state
+                .lock()
+                .unwrap()
+                .iomaps
+                .get_mut(&command::MODULE.id)
+                .unwrap()[offset..offset + data.len()]
+                .copy_from_slice(data);
+        };
+
+        set(command::TICK.offset, &1234u32.to_le_bytes());
+        assert_eq!(
+            nxt.get_vm_status().await.unwrap(),
+            VmStatus {
+                program: None,
+                state: ProgStatus::Idle,
+                tick: 1234,
+            }
+        );
+
+        let mut statuses = Box::pin(nxt.watch_vm(Duration::from_millis(1)));
+        let first = statuses.next().await.unwrap().unwrap();
+        assert_eq!(first.state, ProgStatus::Idle);
+
+        set(command::FILE_NAME.offset, b"test.rxe\0");
+        set(command::PROG_STATUS.offset, &[ProgStatus::Running as u8]);
+        let running = statuses.next().await.unwrap().unwrap();
+        assert!(running.is_running());
+        assert_eq!(running.program.as_deref(), Some("test.rxe"));
+
+        // only the tick changing isn't reported
+        set(command::TICK.offset, &5678u32.to_le_bytes());
+        time::timeout(Duration::from_millis(20), statuses.next())
+            .await
+            .unwrap_err();
+
+        set(command::PROG_STATUS.offset, &[ProgStatus::Error as u8]);
+        let stopped = statuses.next().await.unwrap().unwrap();
+        assert_eq!(stopped.state, ProgStatus::Error);
+        assert_eq!(stopped.tick, 5678);
+    }
+}